@@ -0,0 +1,777 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use proc_macro2::{Literal, Span, TokenStream};
+use quote::quote;
+use serde_json::Value;
+use syn::{DeriveInput, Ident, LitStr};
+
+use crate::nodes::{
+    ArithOp, Attribute, Call, ClassBinding, CompareOp, Condition, ForLoop, If, Include, Iterable,
+    LocalValue, LoopLocal, Node, Operand, StyleBinding, Tag,
+};
+use crate::output::OutputMode;
+use crate::parser::{ParseOptions, parse_template_with_errors};
+
+// Guards against templates that `@defer` themselves
+const MAX_DEFER_DEPTH: usize = 32;
+
+pub fn expand(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let span = input.ident.span();
+    let attr = TemplateAttr::parse(input)?;
+
+    let mut generator = Generator {
+        root: attr.root,
+        span,
+        tracked: Vec::new(),
+        partials: HashMap::new(),
+        mode: OutputMode::Html,
+    };
+
+    let nodes = match attr.source {
+        TemplateSource::Path(key) => {
+            generator.mode = OutputMode::from_key(&key);
+            generator.load(&key)?
+        }
+        TemplateSource::Inline(source) => parse("inline template", &source, span)?,
+    };
+    let body = generator.nodes(&nodes, &Scope::root(), 0)?;
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let tracked = &generator.tracked;
+
+    // 'include_str!' makes cargo rebuild when a template changes
+    Ok(quote! {
+        const _: () = {
+            #( let _ = include_str!(#tracked); )*
+        };
+
+        impl #impl_generics ::skabelon::Template for #ident #ty_generics #where_clause {
+            #[allow(unused_variables, unused_parens, clippy::all)]
+            fn render_to(&self, __out: &mut dyn ::std::fmt::Write) -> ::std::fmt::Result {
+                #body
+                Ok(())
+            }
+        }
+    })
+}
+
+enum TemplateSource {
+    Path(String),
+    Inline(String),
+}
+
+struct TemplateAttr {
+    source: TemplateSource,
+    root: PathBuf,
+}
+
+impl TemplateAttr {
+    /// Parse `#[template(path = "...", root = "...")]` or `#[template(source = "...")]`
+    fn parse(input: &DeriveInput) -> syn::Result<Self> {
+        let mut path: Option<LitStr> = None;
+        let mut source: Option<LitStr> = None;
+        let mut root: Option<LitStr> = None;
+
+        for attr in input.attrs.iter().filter(|a| a.path().is_ident("template")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("path") {
+                    path = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("source") {
+                    source = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("root") {
+                    root = Some(meta.value()?.parse()?);
+                } else {
+                    return Err(meta.error("expected `path`, `source` or `root`"));
+                }
+                Ok(())
+            })?;
+        }
+
+        let manifest_dir = crate::manifest_dir();
+
+        match (path, source) {
+            (Some(path), None) => {
+                let absolute = manifest_dir.join(path.value());
+                // '@defer' keys are relative to the template's directory unless 'root' is given
+                let root = match root {
+                    Some(root) => manifest_dir.join(root.value()),
+                    None => absolute
+                        .parent()
+                        .map(PathBuf::from)
+                        .unwrap_or(manifest_dir.clone()),
+                };
+                let key = absolute
+                    .strip_prefix(&root)
+                    .map(|p| p.to_string_lossy().replace('\\', "/"))
+                    .map_err(|_| syn::Error::new(path.span(), "`path` must be inside `root`"))?;
+                Ok(Self {
+                    source: TemplateSource::Path(key),
+                    root,
+                })
+            }
+            (None, Some(source)) => Ok(Self {
+                source: TemplateSource::Inline(source.value()),
+                root: root
+                    .map(|root| manifest_dir.join(root.value()))
+                    .unwrap_or(manifest_dir),
+            }),
+            _ => Err(syn::Error::new(
+                input.ident.span(),
+                "expected `#[template(path = \"...\")]` or `#[template(source = \"...\")]`",
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Local {
+    /// Bound to a reference, like loop items
+    Ref,
+    Value,
+}
+
+#[derive(Clone)]
+struct Scope {
+    locals: Vec<(String, Local)>,
+    /// Partials only see their local context, not the struct
+    self_access: bool,
+    has_content: bool,
+}
+
+impl Scope {
+    fn root() -> Self {
+        Self {
+            locals: Vec::new(),
+            self_access: true,
+            has_content: false,
+        }
+    }
+
+    fn local(&self, name: &str) -> Option<Local> {
+        self.locals
+            .iter()
+            .rev()
+            .find(|(n, _)| n == name)
+            .map(|(_, l)| *l)
+    }
+
+    fn with(&self, name: &str, local: Local) -> Self {
+        let mut scope = self.clone();
+        scope.locals.push((name.to_string(), local));
+        scope
+    }
+}
+
+struct Generator {
+    root: PathBuf,
+    span: Span,
+    tracked: Vec<String>,
+    partials: HashMap<String, Vec<Node>>,
+    /// The output mode of the template being generated, from its key like the runtime
+    mode: OutputMode,
+}
+
+impl Generator {
+    fn load(&mut self, key: &str) -> syn::Result<Vec<Node>> {
+        let key = key.strip_prefix("./").unwrap_or(key);
+        if let Some(nodes) = self.partials.get(key) {
+            return Ok(nodes.clone());
+        }
+
+        let path = self.root.join(key);
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| self.error(format!("Failed to read {}: {}", path.display(), e)))?;
+        let nodes = parse(key, &content, self.span)?;
+
+        self.tracked.push(path.to_string_lossy().to_string());
+        self.partials.insert(key.to_string(), nodes.clone());
+        Ok(nodes)
+    }
+
+    fn error(&self, message: impl std::fmt::Display) -> syn::Error {
+        syn::Error::new(self.span, message)
+    }
+
+    fn nodes(&mut self, nodes: &[Node], scope: &Scope, depth: usize) -> syn::Result<TokenStream> {
+        let mut out = TokenStream::new();
+        for n in nodes {
+            out.extend(self.node(n, scope, depth)?);
+        }
+        Ok(out)
+    }
+
+    fn node(&mut self, node: &Node, scope: &Scope, depth: usize) -> syn::Result<TokenStream> {
+        match node {
+            Node::Text(s) => Ok(quote!(::std::fmt::Write::write_str(__out, #s)?;)),
+
+            Node::VariableBlock(path) if path.len() == 1 && path[0] == "__CONTENT__" => {
+                Ok(content(scope))
+            }
+
+            Node::VariableBlock(path) => {
+                let value = self.path(path, scope)?;
+                Ok(self.write_value(value))
+            }
+
+            Node::Call(call) => {
+                let value = self.call(call, scope)?;
+                Ok(self.write_value(value))
+            }
+
+            Node::ContentPlaceholder => Ok(content(scope)),
+
+            Node::Tag(Tag {
+                name,
+                attributes,
+                self_closing,
+            }) => {
+                let open = format!("<{}", name);
+                let mut out = quote!(::std::fmt::Write::write_str(__out, #open)?;);
+                for attribute in attributes {
+                    out.extend(match attribute {
+                        Attribute::Static { name, value: None } => {
+                            let name = format!(" {}", name);
+                            quote!(::std::fmt::Write::write_str(__out, #name)?;)
+                        }
+                        Attribute::Static {
+                            name,
+                            value: Some(value),
+                        } => {
+                            let name = format!(" {}=\"", name);
+                            let value = self.nodes(value, scope, depth)?;
+                            quote! {
+                                ::std::fmt::Write::write_str(__out, #name)?;
+                                #value
+                                ::std::fmt::Write::write_str(__out, "\"")?;
+                            }
+                        }
+                        Attribute::Bound {
+                            name,
+                            expr,
+                            boolean: true,
+                        } => {
+                            let cond = self.condition(expr, scope)?;
+                            let name = format!(" {}", name);
+                            quote!(if #cond { ::std::fmt::Write::write_str(__out, #name)?; })
+                        }
+                        Attribute::Bound { name, expr, .. } => {
+                            let value = self.binding(expr, scope)?;
+                            let name = format!(" {}=\"", name);
+                            quote! {
+                                ::std::fmt::Write::write_str(__out, #name)?;
+                                ::skabelon::typed::RenderValue::render_value(
+                                    &(#value),
+                                    &mut ::skabelon::typed::EscapeAttr(&mut *__out),
+                                )?;
+                                ::std::fmt::Write::write_str(__out, "\"")?;
+                            }
+                        }
+                        Attribute::Class(bindings) => {
+                            let mut steps = TokenStream::new();
+                            for binding in bindings {
+                                steps.extend(match binding {
+                                    ClassBinding::Static(value) => {
+                                        let value = self.nodes(value, scope, depth)?;
+                                        quote! {
+                                            let mut __names = String::new();
+                                            {
+                                                let __out: &mut dyn ::std::fmt::Write = &mut __names;
+                                                #value
+                                            }
+                                            __classes.add(&__names);
+                                        }
+                                    }
+                                    ClassBinding::Toggle { name, cond } => {
+                                        let cond = self.condition(cond, scope)?;
+                                        quote!(__classes.toggle(#name, #cond);)
+                                    }
+                                    ClassBinding::Expr(_) => {
+                                        return Err(self.error(
+                                            "`[ngClass]` needs an object literal in typed templates",
+                                        ));
+                                    }
+                                });
+                            }
+                            quote! {
+                                {
+                                    let mut __classes = ::skabelon::typed::ClassList::default();
+                                    #steps
+                                    __classes.write_attribute(__out)?;
+                                }
+                            }
+                        }
+                        Attribute::Style(bindings) => {
+                            let mut steps = TokenStream::new();
+                            for binding in bindings {
+                                steps.extend(match binding {
+                                    StyleBinding::Static(value) => {
+                                        let value = self.nodes(value, scope, depth)?;
+                                        quote! {
+                                            let mut __css = String::new();
+                                            {
+                                                let __out: &mut dyn ::std::fmt::Write = &mut __css;
+                                                #value
+                                            }
+                                            __style.add_declarations(&__css);
+                                        }
+                                    }
+                                    StyleBinding::Property { name, value } => {
+                                        let value = self.binding(value, scope)?;
+                                        quote! {
+                                            let mut __value = String::new();
+                                            ::skabelon::typed::RenderValue::render_value(
+                                                &(#value),
+                                                &mut __value,
+                                            )?;
+                                            __style.set(#name, &__value);
+                                        }
+                                    }
+                                    StyleBinding::Expr(_) => {
+                                        return Err(self.error(
+                                            "`[ngStyle]` needs an object literal in typed templates",
+                                        ));
+                                    }
+                                });
+                            }
+                            quote! {
+                                {
+                                    let mut __style = ::skabelon::typed::StyleList::default();
+                                    #steps
+                                    __style.write_attribute(__out)?;
+                                }
+                            }
+                        }
+                    });
+                }
+                let close = if *self_closing { "/>" } else { ">" };
+                out.extend(quote!(::std::fmt::Write::write_str(__out, #close)?;));
+                Ok(out)
+            }
+
+            Node::If(If {
+                conditions,
+                otherwise,
+            }) => {
+                let mut branches = Vec::new();
+                for (cond, body) in conditions {
+                    let cond = self.condition(cond, scope)?;
+                    let body = self.nodes(body, scope, depth)?;
+                    branches.push(quote!(if #cond { #body }));
+                }
+                let otherwise = match otherwise {
+                    Some(body) => {
+                        let body = self.nodes(body, scope, depth)?;
+                        quote!(else { #body })
+                    }
+                    None => TokenStream::new(),
+                };
+                Ok(quote!(#( #branches )else* #otherwise))
+            }
+
+            Node::Forloop(for_loop) => {
+                let ForLoop {
+                    value,
+                    container,
+                    filter,
+                    limit,
+                    aliases,
+                    body,
+                    ..
+                } = for_loop.as_ref();
+                let item = self.ident(value)?;
+                let (mut items, local) = match container {
+                    Iterable::Path(path) => {
+                        let container = self.path(path, scope)?;
+                        (quote!((&(#container)).into_iter()), Local::Ref)
+                    }
+                    Iterable::Range { start, end, step } => {
+                        let start = self.operand(start, scope, false)?;
+                        let end = self.operand(end, scope, false)?;
+                        let step = match step {
+                            Some(step) => self.operand(step, scope, false)?,
+                            None => quote!(1),
+                        };
+                        let range = quote!(::skabelon::typed::range(
+                            (#start) as i64,
+                            (#end) as i64,
+                            (#step) as i64
+                        ));
+                        (range, Local::Value)
+                    }
+                };
+                if let Some(filter) = filter {
+                    let cond = self.condition(filter, &scope.with(value, local))?;
+                    // `filter` passes a reference to the item
+                    items = quote!(#items.filter(|#item| {
+                        let #item = *#item;
+                        #cond
+                    }));
+                }
+                if let Some(limit) = limit {
+                    let limit = self.operand(limit, scope, false)?;
+                    items = quote!(#items.take((#limit) as usize));
+                }
+                let mut scope = scope.with("index", Local::Value).with(value, local);
+                let mut locals = Vec::new();
+                for (name, local) in aliases {
+                    let ident = self.ident(name)?;
+                    let value = match local {
+                        LoopLocal::Index => quote!(index),
+                        LoopLocal::First => quote!(index == 0),
+                        LoopLocal::Last => quote!(index + 1 == __count),
+                        LoopLocal::Even => quote!(index % 2 == 0),
+                        LoopLocal::Odd => quote!(index % 2 == 1),
+                        LoopLocal::Count => quote!(__count),
+                    };
+                    locals.push(quote!(let #ident = #value;));
+                    scope = scope.with(name, Local::Value);
+                }
+                let body = self.nodes(body, &scope, depth)?;
+                // `last` and `count` need the number of items up front, which is counted in a pass
+                // of its own instead of holding the items
+                let needs_count = aliases
+                    .iter()
+                    .any(|(_, local)| matches!(local, LoopLocal::Last | LoopLocal::Count));
+                if needs_count {
+                    return Ok(quote! {{
+                        let __count = #items.count();
+                        for (index, #item) in #items.enumerate() {
+                            #( #locals )*
+                            #body
+                        }
+                    }});
+                }
+                Ok(quote! {
+                    for (index, #item) in #items.enumerate() {
+                        #( #locals )*
+                        #body
+                    }
+                })
+            }
+
+            // Typed templates render eagerly, so '@placeholder' and friends are not needed
+            Node::Include(Include { path, body, .. }) if path.is_empty() => {
+                self.nodes(body, scope, depth)
+            }
+
+            Node::Include(Include {
+                path,
+                body,
+                local_ctx,
+                ..
+            }) => {
+                if depth >= MAX_DEFER_DEPTH {
+                    return Err(self.error(format!("`@defer({})` nests too deep", path)));
+                }
+                let partial = self.load(path).map_err(|_| {
+                    self.error(format!("`@defer` target `{}` does not exist", path))
+                })?;
+
+                let content = self.nodes(body, scope, depth)?;
+
+                let mut names = Vec::new();
+                let mut values = Vec::new();
+                let mut partial_scope = Scope {
+                    locals: Vec::new(),
+                    self_access: false,
+                    has_content: true,
+                };
+                for (k, local_val) in local_ctx {
+                    names.push(self.ident(k)?);
+                    match local_val {
+                        LocalValue::Literal(Value::Null) => {
+                            values.push(quote!(::std::option::Option::<&str>::None));
+                            partial_scope = partial_scope.with(k, Local::Value);
+                        }
+                        LocalValue::Literal(v) => {
+                            values.push(self.literal(v)?);
+                            partial_scope = partial_scope.with(k, Local::Value);
+                        }
+                        LocalValue::Path(p) => {
+                            let value = self.path(p, scope)?;
+                            values.push(quote!(&(#value)));
+                            partial_scope = partial_scope.with(k, Local::Ref);
+                        }
+                    }
+                }
+
+                // Partials are written in their own mode
+                let mode = std::mem::replace(&mut self.mode, OutputMode::from_key(path));
+                let partial = self.nodes(&partial, &partial_scope, depth + 1);
+                self.mode = mode;
+                let partial = partial?;
+
+                // Locals are bound together so they cannot shadow each other's values
+                Ok(quote! {
+                    {
+                        let __content: ::std::string::String = {
+                            let mut __buf = ::std::string::String::new();
+                            {
+                                let __out = &mut __buf;
+                                #content
+                            }
+                            __buf
+                        };
+                        let (#( #names, )*) = (#( #values, )*);
+                        #partial
+                    }
+                })
+            }
+        }
+    }
+
+    /// Write an interpolated value, escaped for the output mode
+    fn write_value(&self, value: TokenStream) -> TokenStream {
+        match self.mode {
+            OutputMode::Html | OutputMode::Text => {
+                quote!(::skabelon::typed::RenderValue::render_value(&(#value), __out)?;)
+            }
+            OutputMode::Xml => quote!(::skabelon::typed::RenderValue::render_value(
+                &(#value),
+                &mut ::skabelon::typed::EscapeXml(&mut *__out),
+            )?;),
+            OutputMode::Markdown => quote!(::skabelon::typed::RenderValue::render_value(
+                &(#value),
+                &mut ::skabelon::typed::EscapeMarkdown(&mut *__out),
+            )?;),
+            // Values are written as JSON literals, so they have to implement `Serialize`
+            OutputMode::Json => quote!(::skabelon::typed::write_json(&(#value), __out)?;),
+        }
+    }
+
+    fn condition(&self, cond: &Condition, scope: &Scope) -> syn::Result<TokenStream> {
+        match cond {
+            Condition::Literal(Value::Null) => Ok(quote!(false)),
+            Condition::Literal(v) => {
+                let value = self.literal(v)?;
+                Ok(quote!(::skabelon::typed::Truthy::truthy(&(#value))))
+            }
+            Condition::Path(path) => {
+                let value = self.path(path, scope)?;
+                Ok(quote!(::skabelon::typed::Truthy::truthy(&(#value))))
+            }
+            Condition::Operand(operand) => {
+                let value = self.operand(operand, scope, false)?;
+                Ok(quote!(::skabelon::typed::Truthy::truthy(&(#value))))
+            }
+            Condition::And(conds) => {
+                let conds = conds
+                    .iter()
+                    .map(|c| self.condition(c, scope))
+                    .collect::<syn::Result<Vec<_>>>()?;
+                Ok(quote!((#( (#conds) )&&*)))
+            }
+            Condition::Or(conds) => {
+                let conds = conds
+                    .iter()
+                    .map(|c| self.condition(c, scope))
+                    .collect::<syn::Result<Vec<_>>>()?;
+                Ok(quote!((#( (#conds) )||*)))
+            }
+            Condition::Not(inner) => {
+                let inner = self.condition(inner, scope)?;
+                Ok(quote!(!(#inner)))
+            }
+            Condition::Compare {
+                left,
+                op: op @ (CompareOp::In | CompareOp::Contains),
+                right,
+            } => {
+                let (needle, haystack) = match op {
+                    CompareOp::In => (left, right),
+                    _ => (right, left),
+                };
+                let needle = self.operand(needle, scope, false)?;
+                let haystack = self.operand(haystack, scope, false)?;
+                Ok(quote!(::skabelon::typed::Contains::contains_value(&(#haystack), &(#needle))))
+            }
+            Condition::Compare {
+                left,
+                op: op @ (CompareOp::StartsWith | CompareOp::EndsWith),
+                right,
+            } => {
+                let left = self.operand(left, scope, true)?;
+                let right = self.operand(right, scope, true)?;
+                let method = match op {
+                    CompareOp::StartsWith => quote!(starts_with),
+                    _ => quote!(ends_with),
+                };
+                Ok(quote!((#left).#method(#right)))
+            }
+            Condition::Compare { left, op, right } => {
+                let is_str = |o: &Operand| matches!(o, Operand::Literal(Value::String(_)));
+                let compare_str = is_str(left) || is_str(right);
+
+                let left = self.operand(left, scope, compare_str)?;
+                let right = self.operand(right, scope, compare_str)?;
+                let op = match op {
+                    CompareOp::Eq => quote!(==),
+                    CompareOp::Ne => quote!(!=),
+                    CompareOp::Lt => quote!(<),
+                    CompareOp::Gt => quote!(>),
+                    CompareOp::Le => quote!(<=),
+                    CompareOp::Ge => quote!(>=),
+                    _ => unreachable!("handled above"),
+                };
+                Ok(quote!((#left #op #right)))
+            }
+        }
+    }
+
+    fn operand(&self, operand: &Operand, scope: &Scope, as_str: bool) -> syn::Result<TokenStream> {
+        match operand {
+            Operand::Literal(v) => self.literal(v),
+            Operand::Path(path) => {
+                let mut value = self.path(path, scope)?;
+                // Loop items are references, compare the values they point to
+                if path.len() == 1 && scope.local(&path[0]) == Some(Local::Ref) {
+                    value = quote!((*#value));
+                }
+                if as_str {
+                    value = quote!(::std::convert::AsRef::<str>::as_ref(&(#value)));
+                }
+                Ok(value)
+            }
+            Operand::Call(call) => {
+                let mut value = self.call(call, scope)?;
+                if as_str {
+                    value = quote!(::std::convert::AsRef::<str>::as_ref(&(#value)));
+                }
+                Ok(value)
+            }
+            Operand::Arith { left, op, right } => {
+                let left = self.operand(left, scope, false)?;
+                let right = self.operand(right, scope, false)?;
+                let op = match op {
+                    ArithOp::Add => quote!(+),
+                    ArithOp::Sub => quote!(-),
+                    ArithOp::Mul => quote!(*),
+                    ArithOp::Div => quote!(/),
+                    ArithOp::Rem => quote!(%),
+                };
+                Ok(quote!((#left #op #right)))
+            }
+        }
+    }
+
+    /// The value of a `[name]="expr"` binding, a `bool` for comparisons
+    fn binding(&self, expr: &Condition, scope: &Scope) -> syn::Result<TokenStream> {
+        match expr {
+            Condition::Literal(v) => self.literal(v),
+            Condition::Path(path) => self.path(path, scope),
+            Condition::Operand(operand) => self.operand(operand, scope, false),
+            cond => self.condition(cond, scope),
+        }
+    }
+
+    /// Calls are methods on the struct. Fields are passed by reference, literals by value.
+    fn call(&self, call: &Call, scope: &Scope) -> syn::Result<TokenStream> {
+        if !scope.self_access {
+            return Err(self.error(format!(
+                "`{}()` can't be called in a partial, pass the result with `@defer(key; value=...)`",
+                call.name
+            )));
+        }
+        if !call.named.is_empty() {
+            return Err(self.error(format!(
+                "`{}()`: named arguments are not supported in typed templates",
+                call.name
+            )));
+        }
+
+        let name = self.ident(&call.name)?;
+        let args = call
+            .args
+            .iter()
+            .map(|arg| match arg {
+                Operand::Path(path) => {
+                    let value = self.path(path, scope)?;
+                    Ok(quote!(&(#value)))
+                }
+                other => self.operand(other, scope, false),
+            })
+            .collect::<syn::Result<Vec<_>>>()?;
+        Ok(quote!(self.#name(#( #args ),*)))
+    }
+
+    fn literal(&self, value: &Value) -> syn::Result<TokenStream> {
+        match value {
+            Value::String(s) => Ok(quote!(#s)),
+            Value::Bool(b) => Ok(quote!(#b)),
+            Value::Number(n) => {
+                let lit = if let Some(i) = n.as_i64() {
+                    Literal::i64_unsuffixed(i)
+                } else if let Some(u) = n.as_u64() {
+                    Literal::u64_unsuffixed(u)
+                } else {
+                    Literal::f64_unsuffixed(n.as_f64().unwrap_or(0.0))
+                };
+                Ok(quote!(#lit))
+            }
+            other => Err(self.error(format!("`{}` is not supported in typed templates", other))),
+        }
+    }
+
+    /// Turn a template path into a place expression, checked by the compiler against the struct
+    fn path(&self, path: &[String], scope: &Scope) -> syn::Result<TokenStream> {
+        let Some((first, rest)) = path.split_first() else {
+            return Err(self.error("empty variable"));
+        };
+
+        let mut out = if scope.local(first).is_some() {
+            let ident = self.ident(first)?;
+            quote!(#ident)
+        } else if scope.self_access {
+            let ident = self.ident(first)?;
+            quote!(self.#ident)
+        } else {
+            return Err(self.error(format!(
+                "unknown variable `{}` in partial, pass it with `@defer(key; {}=...)`",
+                first, first
+            )));
+        };
+
+        for segment in rest {
+            if let Ok(index) = segment.parse::<usize>() {
+                let index = Literal::usize_unsuffixed(index);
+                out = quote!(#out[#index]);
+            } else {
+                let ident = self.ident(segment)?;
+                out = quote!(#out.#ident);
+            }
+        }
+        Ok(out)
+    }
+
+    fn ident(&self, name: &str) -> syn::Result<Ident> {
+        syn::parse_str::<Ident>(name)
+            .map(|mut ident| {
+                ident.set_span(self.span);
+                ident
+            })
+            .map_err(|_| self.error(format!("`{}` is not a valid field name", name)))
+    }
+}
+
+fn content(scope: &Scope) -> TokenStream {
+    if scope.has_content {
+        quote!(::std::fmt::Write::write_str(__out, &__content)?;)
+    } else {
+        TokenStream::new()
+    }
+}
+
+fn parse(key: &str, source: &str, span: Span) -> syn::Result<Vec<Node>> {
+    let (nodes, errors) = parse_template_with_errors(source, &ParseOptions::default());
+    if errors.is_empty() {
+        Ok(nodes)
+    } else {
+        let errors: Vec<String> = errors.iter().map(|e| format!("{}:{}", key, e)).collect();
+        Err(syn::Error::new(
+            span,
+            format!("invalid template:\n{}", errors.join("\n")),
+        ))
+    }
+}
@@ -0,0 +1,254 @@
+use std::path::{Path, PathBuf};
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::{DeriveInput, Ident, LitBool, LitStr, Token, parse_macro_input};
+
+mod derive;
+
+// The macros validate templates with the same parser the runtime uses
+use skabelon_parser::{nodes, output, parser, validate};
+
+/// Embed templates matching a glob into the binary.
+///
+/// The pattern is resolved relative to the crate's `Cargo.toml`. Release builds
+/// include every match with `include_str!`, debug builds read them from disk with
+/// `Templates::load_glob` so `templates.reload()` keeps working.
+/// Templates are validated like with `validate!`.
+///
+/// Release builds can't `reload()` embedded templates, and parse options must be given to the
+/// macro, because the templates are loaded before `set_minify` and friends can be called:
+/// `html_mode`, `minify`, `strip_comments` and `preserve_whitespace`, each optionally `= false`.
+///
+/// Example:
+///     let templates = skabelon::embed!("templates/**/*.html", minify, html_mode);
+///     // Keys become like "partials/card.html", "main.html"
+#[proc_macro]
+pub fn embed(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as GlobInput);
+
+    match expand_embed(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+/// Parse every template matching a glob at compile time.
+///
+/// The build fails with the parser's diagnostics if a template has syntax errors
+/// or a `@defer` target is not among the matched templates.
+/// Takes the same parse options as `embed!`.
+///
+/// Example:
+///     skabelon::validate!("templates/**/*.html", html_mode);
+#[proc_macro]
+pub fn validate(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as GlobInput);
+
+    match expand_validate(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+/// A glob pattern followed by parse options, like `"templates/*.html", minify`
+struct GlobInput {
+    pattern: LitStr,
+    options: Vec<(Ident, bool)>,
+}
+
+impl Parse for GlobInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let pattern = input.parse()?;
+        let mut options = Vec::new();
+        while input.parse::<Option<Token![,]>>()?.is_some() && !input.is_empty() {
+            let name: Ident = input.parse()?;
+            if !matches!(
+                name.to_string().as_str(),
+                "html_mode" | "minify" | "strip_comments" | "preserve_whitespace"
+            ) {
+                return Err(syn::Error::new(
+                    name.span(),
+                    "expected `html_mode`, `minify`, `strip_comments` or `preserve_whitespace`",
+                ));
+            }
+            let value = match input.parse::<Option<Token![=]>>()? {
+                Some(_) => input.parse::<LitBool>()?.value,
+                None => true,
+            };
+            options.push((name, value));
+        }
+        Ok(Self { pattern, options })
+    }
+}
+
+impl GlobInput {
+    fn parse_options(&self) -> parser::ParseOptions {
+        let mut options = parser::ParseOptions::default();
+        for (name, value) in &self.options {
+            match name.to_string().as_str() {
+                "html_mode" => options.html = *value,
+                "minify" => options.minify = *value,
+                "strip_comments" => options.strip_comments = *value,
+                _ => options.collapse_whitespace = !*value,
+            }
+        }
+        options
+    }
+
+    /// `templates.set_minify(true);` and so on
+    fn setters(&self) -> proc_macro2::TokenStream {
+        let setters = self.options.iter().map(|(name, value)| {
+            let setter = Ident::new(&format!("set_{}", name), name.span());
+            quote!(templates.#setter(#value);)
+        });
+        quote!(#( #setters )*)
+    }
+}
+
+fn expand_validate(input: &GlobInput) -> syn::Result<proc_macro2::TokenStream> {
+    let (_, sources) = load_sources(&input.pattern, &input.parse_options())?;
+    let paths = sources.iter().map(|source| &source.path);
+
+    // 'include_str!' makes cargo rebuild when a template changes
+    Ok(quote! {
+        const _: () = {
+            #( let _ = include_str!(#paths); )*
+        };
+    })
+}
+
+fn expand_embed(input: &GlobInput) -> syn::Result<proc_macro2::TokenStream> {
+    let (absolute_pattern, sources) = load_sources(&input.pattern, &input.parse_options())?;
+    let keys = sources.iter().map(|source| &source.key);
+    let paths = sources.iter().map(|source| &source.path);
+    let setters = input.setters();
+
+    Ok(quote! {
+        {
+            #[cfg(debug_assertions)]
+            let templates = {
+                let mut templates = ::skabelon::Templates::new();
+                #setters
+                templates.load_glob(#absolute_pattern);
+                templates
+            };
+
+            #[cfg(not(debug_assertions))]
+            let templates = {
+                let mut templates = ::skabelon::Templates::new();
+                #setters
+                #( templates.load_str(#keys, include_str!(#paths)); )*
+                templates
+            };
+
+            templates
+        }
+    })
+}
+
+/// Compile a template into Rust code rendering the fields of a struct.
+///
+/// Variables are checked against the struct's fields at compile time.
+///
+/// Example:
+///     #[derive(Template)]
+///     #[template(path = "templates/page.html")]
+///     struct Page { title: String, items: Vec<String> }
+///     // page.render() returns the html
+#[proc_macro_derive(Template, attributes(template))]
+pub fn derive_template(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match derive::expand(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+struct Source {
+    key: String,
+    path: String,
+}
+
+/// Read and validate all templates matching the pattern
+fn load_sources(
+    pattern: &LitStr,
+    options: &parser::ParseOptions,
+) -> syn::Result<(String, Vec<Source>)> {
+    let absolute_pattern = manifest_dir().join(pattern.value());
+    let absolute_pattern = absolute_pattern.to_string_lossy().replace('\\', "/");
+    let base_dir = PathBuf::from(derive_base_dir(&absolute_pattern));
+
+    let entries = glob::glob(&absolute_pattern)
+        .map_err(|e| syn::Error::new(pattern.span(), format!("Invalid glob pattern: {}", e)))?;
+
+    let mut sources = Vec::new();
+    let mut parsed = Vec::new();
+    for entry in entries {
+        let path = entry.map_err(|e| syn::Error::new(pattern.span(), e.to_string()))?;
+        if !path.is_file() {
+            continue;
+        }
+        let content = std::fs::read_to_string(&path).map_err(|e| {
+            syn::Error::new(
+                pattern.span(),
+                format!("Failed to read {}: {}", path.display(), e),
+            )
+        })?;
+        parsed.push(parser::parse_template_with_errors(&content, options));
+        sources.push(Source {
+            key: relative_key(&base_dir, &path),
+            path: path.to_string_lossy().to_string(),
+        });
+    }
+
+    let exists = |path: &str| {
+        let path = path.strip_prefix("./").unwrap_or(path);
+        sources.iter().any(|source| source.key == path)
+    };
+    let diagnostics: Vec<String> = sources
+        .iter()
+        .zip(&parsed)
+        .flat_map(|(source, (nodes, errors))| {
+            validate::diagnose(&source.key, nodes, errors, &exists)
+        })
+        .map(|diagnostic| diagnostic.to_string())
+        .collect();
+
+    if !diagnostics.is_empty() {
+        return Err(syn::Error::new(
+            pattern.span(),
+            format!("invalid templates:\n{}", diagnostics.join("\n")),
+        ));
+    }
+
+    Ok((absolute_pattern, sources))
+}
+
+fn manifest_dir() -> PathBuf {
+    std::env::var("CARGO_MANIFEST_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from("."))
+}
+
+fn relative_key(base_dir: &Path, path: &Path) -> String {
+    let rel = path.strip_prefix(base_dir).unwrap_or(path);
+    rel.to_string_lossy().replace('\\', "/")
+}
+
+fn derive_base_dir(pattern: &str) -> String {
+    // Must match `Templates::load_glob` so debug and release builds agree on keys
+    if let Some(idx) = pattern.find('*') {
+        let base = &pattern[..idx];
+        let base = base.trim_end_matches('/');
+        base.to_string()
+    } else {
+        let path = Path::new(pattern);
+        path.parent()
+            .unwrap_or_else(|| Path::new("."))
+            .to_string_lossy()
+            .to_string()
+    }
+}
@@ -0,0 +1,9 @@
+//! The template parser of skabelon, shared by the runtime engine and the macros.
+//!
+//! This crate is an implementation detail of `skabelon` and has no stable API of its own.
+
+pub mod nodes;
+pub mod output;
+pub mod parser;
+pub mod validate;
+pub mod visit;
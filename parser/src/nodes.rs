@@ -0,0 +1,183 @@
+use serde_json::Value;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Node {
+    Text(String),
+    VariableBlock(Vec<String>),
+    Call(Call),
+    Forloop(Box<ForLoop>),
+    If(If),
+    Include(Include),
+    Tag(Tag),
+    ContentPlaceholder,
+}
+
+/// A start tag with bound attributes, like `<input [value]="name">`.
+/// Tags without bindings stay in `Node::Text`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tag {
+    pub name: String,
+    pub attributes: Vec<Attribute>,
+    pub self_closing: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Attribute {
+    /// `name` or `name="value"`, where the value can contain interpolations
+    Static {
+        name: String,
+        value: Option<Vec<Node>>,
+    },
+    /// `[name]="expr"`. Boolean attributes are written without a value when true.
+    Bound {
+        name: String,
+        expr: Condition,
+        boolean: bool,
+    },
+    /// `class`, merged from the static attribute, `[class]`, `[class.name]` and `[ngClass]`
+    Class(Vec<ClassBinding>),
+    /// `style`, merged from the static attribute, `[style]`, `[style.property]` and `[ngStyle]`
+    Style(Vec<StyleBinding>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ClassBinding {
+    /// `class="btn {{kind}}"`
+    Static(Vec<Node>),
+    /// `[class.active]="cond"`, or an entry of `[ngClass]="{'active': cond}"`.
+    /// Adds the class when true and removes it when false.
+    Toggle { name: String, cond: Condition },
+    /// `[ngClass]="value"` with a string, an array of names or an object of names to conditions
+    Expr(Condition),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum StyleBinding {
+    /// `style="color: red"`
+    Static(Vec<Node>),
+    /// `[style.width.px]="width"`, or an entry of `[ngStyle]`. The unit is optional.
+    Property { name: String, value: Condition },
+    /// `[ngStyle]="value"` with a declarations string or an object of properties to values
+    Expr(Condition),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ForLoop {
+    pub value: String,
+    pub container: Iterable,
+    /// `where item.published`, skipping items for which it is false
+    pub filter: Option<Condition>,
+    /// `limit 5`, the most items to render
+    pub limit: Option<Operand>,
+    /// `track item.id`, the key that identifies an item
+    pub track: Option<Operand>,
+    /// `let i = $index`, other names for the loop variables
+    pub aliases: Vec<(String, LoopLocal)>,
+    pub body: Vec<Node>,
+}
+
+/// `$index`, `$first`, `$last`, `$even`, `$odd` and `$count` of Angular's `@for`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LoopLocal {
+    Index,
+    First,
+    Last,
+    Even,
+    Odd,
+    Count,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Iterable {
+    Path(Vec<String>),
+    /// `range(start, end, step)`, counting from `start` up to but not including `end`
+    Range {
+        start: Operand,
+        end: Operand,
+        step: Option<Operand>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Operand {
+    Path(Vec<String>),
+    Literal(Value),
+    Call(Call),
+    Arith {
+        left: Box<Operand>,
+        op: ArithOp,
+        right: Box<Operand>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ArithOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+/// `name(args, key=value)`, calling a function registered on `Templates`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Call {
+    pub name: String,
+    pub args: Vec<Operand>,
+    pub named: Vec<(String, Operand)>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    /// `needle in haystack`, for arrays, object keys and substrings
+    In,
+    /// `haystack contains needle`
+    Contains,
+    StartsWith,
+    EndsWith,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Condition {
+    Literal(Value),
+    Path(Vec<String>),
+    /// A call or arithmetic, used for its truthiness
+    Operand(Operand),
+    And(Vec<Condition>),
+    Or(Vec<Condition>),
+    Not(Box<Condition>),
+    Compare {
+        left: Operand,
+        op: CompareOp,
+        right: Operand,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct If {
+    pub conditions: Vec<(Condition, Vec<Node>)>,
+    pub otherwise: Option<Vec<Node>>,
+}
+
+/// `@defer`. Without a `path` the body is rendered in place.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Include {
+    pub path: String,
+    pub body: Vec<Node>,
+    pub local_ctx: Vec<(String, LocalValue)>,
+    pub placeholder: Option<Vec<Node>>,
+    pub loading: Option<Vec<Node>>,
+    pub error: Option<Vec<Node>>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LocalValue {
+    Literal(Value),
+    Path(Vec<String>),
+}
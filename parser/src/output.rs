@@ -0,0 +1,33 @@
+//! The output modes of templates, picked by the extension of their key.
+
+use std::path::Path;
+
+/// How the values of a template are written, and what is written for missing ones
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputMode {
+    /// Values are written as they are and missing templates as `<!-- -->` comments
+    #[default]
+    Html,
+    /// `.xml`: values are escaped and missing templates are comments
+    Xml,
+    /// `.txt`: values are written as they are and missing templates are left out
+    Text,
+    /// `.md`: Markdown characters in values are escaped with a backslash
+    Markdown,
+    /// `.json`: values are written as JSON literals and missing values as `null`
+    Json,
+}
+
+impl OutputMode {
+    /// The mode for a template key like `sitemap.xml`, `Html` for unknown extensions
+    pub fn from_key(key: &str) -> Self {
+        let extension = Path::new(key).extension().and_then(|e| e.to_str());
+        match extension.map(str::to_ascii_lowercase).as_deref() {
+            Some("xml") => OutputMode::Xml,
+            Some("txt") => OutputMode::Text,
+            Some("md") => OutputMode::Markdown,
+            Some("json") => OutputMode::Json,
+            _ => OutputMode::Html,
+        }
+    }
+}
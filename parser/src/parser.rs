@@ -0,0 +1,2059 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use serde_json::Value;
+
+use crate::nodes::{
+    ArithOp, Attribute, Call, ClassBinding, CompareOp, Condition, ForLoop, If, Include, Iterable,
+    LocalValue, LoopLocal, Node, Operand, StyleBinding, Tag,
+};
+use crate::visit::blocks_mut;
+
+/// Placeholder path for a `<ng-template>` named by `*ngIf`, filled in after parsing
+const TEMPLATE_REF: &str = "__TEMPLATE__";
+
+// Guards against `<ng-template>` blocks that name themselves
+const MAX_TEMPLATE_DEPTH: usize = 32;
+
+/// Elements without an end tag
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
+/// Elements whose whitespace is kept when whitespace is collapsed
+const PRESERVE_WHITESPACE: &[&str] = &["pre", "textarea", "script", "style"];
+
+/// Elements whose content is text, even in HTML mode
+const RAW_TEXT_ELEMENTS: &[&str] = &["script", "style"];
+
+/// Elements whose comments are kept when comments are stripped, because their content is text or
+/// kept as it is written
+const KEEP_COMMENTS: &[&str] = &["pre", "script", "style", "textarea"];
+
+/// Elements whose end tag HTML lets you leave out
+const OPTIONAL_END_TAGS: &[&str] = &[
+    "caption", "colgroup", "dd", "dt", "li", "optgroup", "option", "p", "rp", "rt", "tbody", "td",
+    "tfoot", "th", "thead", "tr",
+];
+
+/// Attributes that are written without a value when bound to a truthy value, and left out otherwise
+const BOOLEAN_ATTRIBUTES: &[&str] = &[
+    "allowfullscreen",
+    "async",
+    "autofocus",
+    "autoplay",
+    "checked",
+    "controls",
+    "default",
+    "defer",
+    "disabled",
+    "formnovalidate",
+    "hidden",
+    "inert",
+    "ismap",
+    "itemscope",
+    "loop",
+    "multiple",
+    "muted",
+    "nomodule",
+    "novalidate",
+    "open",
+    "playsinline",
+    "readonly",
+    "required",
+    "reversed",
+    "selected",
+];
+
+/// Parse a template, also returning the syntax errors the parser recovered from
+pub fn parse_template_with_errors(
+    input: &str,
+    options: &ParseOptions,
+) -> (Vec<Node>, Vec<ParseError>) {
+    let mut p = Parser::new(input, options);
+    let mut nodes = p.parse_nodes(None);
+    p.expand_template_refs(&mut nodes);
+    if options.collapse_whitespace || options.minify {
+        let mut state = Collapse {
+            preserve: 0,
+            unquote: options.minify,
+        };
+        collapse_whitespace(&mut nodes, &mut state);
+    }
+    (nodes, p.errors)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Settings of `Templates` that change how templates are parsed
+#[derive(Clone, Debug, Default)]
+pub struct ParseOptions {
+    /// Angular-only attributes that are renamed instead of dropped, like `(click)` to `hx-post`
+    pub attribute_map: HashMap<String, String>,
+    /// Follow the markup, so blocks are only recognised where Angular recognises them
+    pub html: bool,
+    /// Leave out `<!-- -->` comments, except conditional comments
+    pub strip_comments: bool,
+    /// Angular's `preserveWhitespaces: false`
+    pub collapse_whitespace: bool,
+    /// Collapse whitespace, strip comments and drop quotes that are not needed
+    pub minify: bool,
+}
+
+/// Where `parse_nodes` is in the markup of a block, in HTML mode
+#[derive(Default)]
+struct Markup {
+    /// Elements opened in this block, with where they start
+    open: Vec<(String, usize)>,
+    /// The start tag being read
+    tag: Option<(String, usize)>,
+    /// The quote of the attribute value being read
+    quote: Option<char>,
+}
+
+/// How far the source has been followed to tell comments from text, in every mode
+#[derive(Default)]
+struct Comments {
+    /// Where following stopped
+    at: usize,
+    /// The start tag being read
+    tag: Option<String>,
+    /// The quote of the attribute value being read
+    quote: Option<char>,
+    /// The element whose content is being read as text
+    text: Option<String>,
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    byte_offset: usize,
+    errors: Vec<ParseError>,
+    options: &'a ParseOptions,
+    /// `<ng-template #name>` blocks
+    templates: HashMap<String, Vec<Node>>,
+    /// Names used by `*ngIf`, with where they are used
+    template_refs: Vec<(String, usize)>,
+    /// An element left open by the last bound start tag
+    opened: Option<(String, usize)>,
+    comments: Comments,
+}
+
+/// An attribute name, the range and quote of its value, and the range of the whole attribute
+type RawAttribute = (String, Option<(usize, usize, Option<char>)>, Range<usize>);
+
+impl<'a> Parser<'a> {
+    fn new(src: &'a str, options: &'a ParseOptions) -> Self {
+        Self {
+            src,
+            byte_offset: 0,
+            errors: Vec::new(),
+            options,
+            templates: HashMap::new(),
+            template_refs: Vec::new(),
+            opened: None,
+            comments: Comments::default(),
+        }
+    }
+
+    fn error_at(&mut self, byte_offset: usize, message: impl Into<String>) {
+        // Offsets like `start - 1` can land inside a multibyte character
+        let before = &self.src[..self.src.floor_char_boundary(byte_offset)];
+        let line = before.matches('\n').count() + 1;
+        let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+        self.errors.push(ParseError {
+            line,
+            column,
+            message: message.into(),
+        });
+    }
+
+    fn parse_defer(&mut self) -> Node {
+        let start = self.byte_offset;
+        self.byte_offset += "@defer".len();
+
+        self.skip_ws();
+
+        // '@defer { ... }' renders its body in place, like Angular
+        let (path, local_ctx) = if self.peek_char() == Some('{') {
+            (String::new(), Vec::new())
+        } else {
+            self.expect_char('(');
+
+            let inner = self.read_until_unbalanced(')', '(');
+            let mut parts = inner.splitn(2, ';').map(|s| s.trim());
+            let path = parts.next().unwrap_or("").to_string();
+            if path.is_empty() {
+                self.error_at(start, "`@defer` is missing a template key");
+            }
+            let local_ctx = parts.next().map(parse_kv_pairs).unwrap_or_default();
+            (path, local_ctx)
+        };
+
+        // Optional block `{ ... }`
+        self.skip_ws();
+        let body = if self.peek_char() == Some('{') {
+            self.parse_block_body()
+        } else {
+            Vec::new()
+        };
+
+        let mut include = Include {
+            path,
+            body,
+            local_ctx,
+            placeholder: None,
+            loading: None,
+            error: None,
+        };
+
+        // Trailing '@placeholder', '@loading' and '@error' blocks, in any order
+        loop {
+            let before_ws = self.byte_offset;
+            self.skip_ws();
+
+            let slot = if self.at_keyword("@placeholder") {
+                self.byte_offset += "@placeholder".len();
+                &mut include.placeholder
+            } else if self.at_keyword("@loading") {
+                self.byte_offset += "@loading".len();
+                &mut include.loading
+            } else if self.at_keyword("@error") {
+                self.byte_offset += "@error".len();
+                &mut include.error
+            } else {
+                // Keep the whitespace, it belongs to the following text
+                self.byte_offset = before_ws;
+                break;
+            };
+
+            // Parameters like '(minimum 500ms)' only matter in the browser
+            self.skip_ws();
+            if self.peek_char() == Some('(') {
+                self.byte_offset += 1;
+                self.read_until_unbalanced(')', '(');
+            }
+
+            *slot = Some(self.parse_block_body());
+        }
+
+        Node::Include(include)
+    }
+
+    fn parse_nodes(&mut self, end_on: Option<char>) -> Vec<Node> {
+        let mut nodes = Vec::new();
+        let mut text_buf = String::new();
+        let start = self.byte_offset;
+        let mut closed = end_on.is_none();
+        let mut markup = Markup::default();
+
+        while !self.eof() {
+            // Blocks are not recognised inside a start tag
+            let in_tag = markup.tag.is_some();
+            // `~}` trims the whitespace at the end of the block
+            if !in_tag
+                && let Some(end) = end_on
+                && self.starts_with("~")
+                && self.src[self.byte_offset + 1..].starts_with(end)
+            {
+                trim_end(&mut nodes, &mut text_buf);
+                self.byte_offset += 1;
+            }
+            if !in_tag
+                && let Some(end) = end_on
+                && self.peek_char() == Some(end)
+            {
+                if !text_buf.is_empty() {
+                    nodes.push(Node::Text(std::mem::take(&mut text_buf)));
+                }
+                self.byte_offset += end.len_utf8(); // consume end
+                closed = true;
+                break;
+            }
+
+            if self.starts_with("{{!--") {
+                self.skip_comment("{{!--", "--}}");
+                continue;
+            }
+
+            // `~@if` trims the whitespace before the block
+            if !in_tag
+                && self.starts_with("~@")
+                && ["@if", "@for", "@defer"]
+                    .iter()
+                    .any(|keyword| self.src[self.byte_offset + 1..].starts_with(keyword))
+            {
+                trim_end(&mut nodes, &mut text_buf);
+                self.byte_offset += 1;
+                continue;
+            }
+
+            if self.starts_with("{{") {
+                if self.starts_with("{{-") {
+                    trim_end(&mut nodes, &mut text_buf);
+                }
+                if !text_buf.is_empty() {
+                    nodes.push(Node::Text(std::mem::take(&mut text_buf)));
+                }
+                nodes.push(self.parse_variable());
+                continue;
+            }
+
+            // `<!--[if IE]>` and `<!--<![endif]-->` are kept
+            if !in_tag
+                && (self.options.strip_comments || self.options.minify)
+                && self.starts_with("<!--")
+                && !self.starts_with("<!--[")
+                && !self.starts_with("<!--<![")
+                && self.at_comment()
+            {
+                self.skip_comment("<!--", "-->");
+                continue;
+            }
+
+            if !in_tag && self.at_keyword("@defer") {
+                if !text_buf.is_empty() {
+                    nodes.push(Node::Text(std::mem::take(&mut text_buf)));
+                }
+                nodes.push(self.parse_defer());
+                continue;
+            }
+
+            if !in_tag && self.at_keyword("@if") {
+                if !text_buf.is_empty() {
+                    nodes.push(Node::Text(std::mem::take(&mut text_buf)));
+                }
+                nodes.push(self.parse_if());
+                continue;
+            }
+
+            if !in_tag && self.at_keyword("@for") {
+                if !text_buf.is_empty() {
+                    nodes.push(Node::Text(std::mem::take(&mut text_buf)));
+                }
+                nodes.push(self.parse_for());
+                continue;
+            }
+
+            if !in_tag
+                && self.peek_char() == Some('<')
+                && let Some(tag) = self.parse_tag()
+            {
+                if !text_buf.is_empty() {
+                    nodes.push(Node::Text(std::mem::take(&mut text_buf)));
+                }
+                nodes.extend(tag);
+                if let Some((name, start)) = self.opened.take()
+                    && self.options.html
+                {
+                    self.open_element(name, start, &mut markup, &mut text_buf);
+                }
+                continue;
+            }
+
+            if self.options.html && self.scan_markup(&mut markup, &mut text_buf) {
+                continue;
+            }
+
+            if !in_tag && self.at_keyword("@else") {
+                if !text_buf.is_empty() {
+                    nodes.push(Node::Text(std::mem::take(&mut text_buf)));
+                }
+                self.error_at(self.byte_offset, "`@else` without a matching `@if`");
+                text_buf.push_str("@else");
+                self.byte_offset += "@else".len();
+                continue;
+            }
+
+            if let Some(ch) = self.peek_char() {
+                text_buf.push(ch);
+                self.advance_one();
+            } else {
+                break;
+            }
+        }
+
+        if !text_buf.is_empty() {
+            nodes.push(Node::Text(text_buf));
+        }
+        if let Some((name, start)) = markup.tag {
+            self.error_at(start, format!("unclosed start tag `<{}`", name));
+        }
+        for (name, start) in markup.open {
+            if !OPTIONAL_END_TAGS.contains(&name.as_str()) {
+                self.error_at(start, format!("unclosed `<{}>`", name));
+            }
+        }
+        if !closed {
+            // 'start' is just past the opening '{'
+            self.error_at(start.saturating_sub(1), "unclosed block, expected `}`");
+        }
+        nodes
+    }
+
+    fn parse_variable(&mut self) -> Node {
+        let open = self.byte_offset;
+        self.byte_offset += 2; // '{{' are ASCII, 2 bytes
+        if self.starts_with("-") {
+            self.byte_offset += 1;
+        }
+        let start = self.byte_offset;
+        let mut quote = None;
+
+        while !self.eof() {
+            // '}}' inside a string like `{{ '}}' }}` doesn't end the interpolation
+            match (quote, self.peek_char()) {
+                (Some(q), Some(c)) if c == q => quote = None,
+                (None, Some(c @ ('"' | '\''))) => quote = Some(c),
+                _ => {}
+            }
+            if quote.is_none() && self.starts_with("}}") {
+                let expr = self.src[start..self.byte_offset].trim();
+                self.byte_offset += 2; // consume '}}'
+                // `-}}` trims the whitespace after the interpolation
+                let trimmed = match expr.strip_suffix('-') {
+                    Some(expr) => {
+                        self.skip_ws();
+                        expr.trim()
+                    }
+                    None => expr,
+                };
+                // `{{ '{' }}` writes a literal, like in Angular
+                if let Some(literal) = string_literal(trimmed) {
+                    return Node::Text(literal.to_string());
+                }
+                if trimmed == "content" {
+                    return Node::VariableBlock(vec!["__CONTENT__".to_string()]);
+                }
+                if let Some(call) = parse_call_expr(trimmed) {
+                    return Node::Call(call);
+                }
+                return Node::VariableBlock(parse_variable_path(trimmed));
+            }
+            self.advance_one();
+        }
+
+        self.error_at(open, "unclosed interpolation, expected `}}`");
+        Node::VariableBlock(parse_variable_path(self.src[start..].trim()))
+    }
+
+    fn parse_if(&mut self) -> Node {
+        self.byte_offset += "@if".len();
+
+        self.skip_ws();
+        self.expect_char('(');
+
+        let expr = self.read_until_unbalanced(')', '(');
+        let cond = parse_bool_expr(expr.trim());
+
+        self.skip_ws();
+        let body = self.parse_block_body();
+
+        let mut conditions = Vec::new();
+        conditions.push((cond, body));
+        let mut otherwise: Option<Vec<Node>> = None;
+
+        loop {
+            self.skip_ws();
+
+            if self.at_keyword("@else") {
+                self.byte_offset += "@else".len();
+                self.skip_ws();
+
+                if self.at_keyword("if") {
+                    // '@else if (...) { ... }'
+                    self.byte_offset += "if".len();
+                    self.skip_ws();
+                    self.expect_char('(');
+                    let expr = self.read_until_unbalanced(')', '(');
+                    let cond = parse_bool_expr(expr.trim());
+
+                    self.skip_ws();
+                    let body = self.parse_block_body();
+
+                    conditions.push((cond, body));
+                    continue;
+                } else {
+                    // '@else { ... }'
+                    self.skip_ws();
+                    let else_body = self.parse_block_body();
+                    otherwise = Some(else_body);
+                    break;
+                }
+            } else {
+                break;
+            }
+        }
+
+        Node::If(If {
+            conditions,
+            otherwise,
+        })
+    }
+
+    fn parse_for(&mut self) -> Node {
+        let start = self.byte_offset;
+        self.byte_offset += "@for".len();
+
+        self.skip_ws();
+        self.expect_char('(');
+
+        let for_expr = self.read_until_unbalanced(')', '(');
+        let mut for_loop = self.parse_for_header(start, &for_expr);
+
+        self.skip_ws();
+        for_loop.body = self.parse_block_body();
+
+        Node::Forloop(Box::new(for_loop))
+    }
+
+    /// `item of items; track item.id`, without the body
+    fn parse_for_header(&mut self, start: usize, for_expr: &str) -> ForLoop {
+        let mut clauses = split_top_level(for_expr, ';').into_iter();
+        let (value, container_str) = parse_for_expression(clauses.next().unwrap_or(""));
+        if value.is_empty() || container_str.is_empty() {
+            self.error_at(
+                start,
+                "`@for` expects `(item in items)` or `(item of items)`",
+            );
+        }
+
+        let container = self.parse_iterable(start, container_str.trim());
+
+        let (mut filter, mut limit, mut track) = (None, None, None);
+        let mut aliases = Vec::new();
+        for clause in clauses.map(str::trim).filter(|c| !c.is_empty()) {
+            let (option, expr) = clause.split_once(' ').unwrap_or((clause, ""));
+            match option {
+                "where" => filter = Some(parse_bool_expr(expr)),
+                "limit" => limit = Some(parse_operand_expr(expr)),
+                "track" => track = Some(parse_operand_expr(expr)),
+                "let" => self.parse_loop_aliases(start, expr, &mut aliases),
+                _ => self.error_at(start, format!("unknown `@for` option `{}`", clause)),
+            }
+        }
+
+        ForLoop {
+            value,
+            container,
+            filter,
+            limit,
+            track,
+            aliases,
+            body: Vec::new(),
+        }
+    }
+
+    /// `let i = $index, last = $last`. The `$` is optional, like in `*ngFor`.
+    fn parse_loop_aliases(
+        &mut self,
+        start: usize,
+        expr: &str,
+        aliases: &mut Vec<(String, LoopLocal)>,
+    ) {
+        for alias in expr.split(',') {
+            let Some((name, local)) = alias.split_once('=') else {
+                self.error_at(
+                    start,
+                    format!("`let` expects `name = $index`, got `{}`", alias.trim()),
+                );
+                continue;
+            };
+            let local = match local.trim().trim_start_matches('$') {
+                "index" => LoopLocal::Index,
+                "first" => LoopLocal::First,
+                "last" => LoopLocal::Last,
+                "even" => LoopLocal::Even,
+                "odd" => LoopLocal::Odd,
+                "count" => LoopLocal::Count,
+                other => {
+                    self.error_at(start, format!("unknown loop variable `{}`", other));
+                    continue;
+                }
+            };
+            aliases.push((name.trim().to_string(), local));
+        }
+    }
+
+    /// `items` or `range(start, end, step)`
+    fn parse_iterable(&mut self, start: usize, expr: &str) -> Iterable {
+        let Some(call) = parse_call_expr(expr).filter(|call| call.name == "range") else {
+            return Iterable::Path(parse_variable_path(expr));
+        };
+
+        if !call.named.is_empty() || call.args.is_empty() || call.args.len() > 3 {
+            self.error_at(
+                start,
+                "`range` expects `(end)`, `(start, end)` or `(start, end, step)`",
+            );
+        }
+        let mut args = call.args.into_iter();
+        let (start, end) = match (args.next(), args.next()) {
+            (Some(end), None) => (Operand::Literal(Value::from(0)), end),
+            (Some(start), Some(end)) => (start, end),
+            _ => (
+                Operand::Literal(Value::from(0)),
+                Operand::Literal(Value::from(0)),
+            ),
+        };
+        Iterable::Range {
+            start,
+            end,
+            step: args.next(),
+        }
+    }
+
+    /// A tag with `[name]="expr"` bindings or structural directives like `*ngIf`.
+    /// Other tags are left to the text.
+    fn parse_tag(&mut self) -> Option<Vec<Node>> {
+        let start = self.byte_offset;
+        let nodes = self.scan_tag();
+        if nodes.is_none() {
+            self.byte_offset = start;
+        }
+        nodes
+    }
+
+    fn scan_tag(&mut self) -> Option<Vec<Node>> {
+        let start = self.byte_offset;
+        self.byte_offset += 1; // '<'
+        let name = self.read_while(|c| c.is_ascii_alphanumeric() || c == '-');
+        if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            return None;
+        }
+
+        // Values are only parsed once the tag is known to need it
+        let mut raw = Vec::new();
+        let self_closing = loop {
+            self.skip_ws();
+            if self.starts_with("/>") {
+                self.byte_offset += 2;
+                break true;
+            }
+            if self.starts_with(">") {
+                self.byte_offset += 1;
+                break false;
+            }
+
+            let attr_start = self.byte_offset;
+            let attr_name = if self.starts_with("[") {
+                let name = self.read_while(|c| c != ']' && c != '>' && !c.is_whitespace());
+                if !self.starts_with("]") {
+                    return None;
+                }
+                self.byte_offset += 1;
+                format!("{}]", name)
+            } else {
+                self.read_while(|c| {
+                    !c.is_whitespace() && !matches!(c, '=' | '>' | '/' | '"' | '\'')
+                })
+            };
+            if attr_name.is_empty() || attr_name.starts_with(['@', '{']) {
+                return None;
+            }
+
+            self.skip_ws();
+            let value = if self.starts_with("=") {
+                self.byte_offset += 1;
+                self.skip_ws();
+                Some(self.scan_attribute_value()?)
+            } else {
+                None
+            };
+            raw.push((attr_name, value, attr_start..self.byte_offset));
+        };
+        let tag_end = self.byte_offset;
+
+        let directive = raw
+            .iter()
+            .position(|(name, ..)| name == "*ngIf" || name == "*ngFor")
+            .map(|i| raw.remove(i));
+        let reference = raw
+            .iter()
+            .find_map(|(name, ..)| name.strip_prefix('#').map(String::from));
+        // A tag that loses or renames attributes is rebuilt like one with bindings
+        let rewritten = self.rewrite_attributes(&mut raw);
+        let is_bound = rewritten || raw.iter().any(|(name, ..)| name.starts_with('['));
+        let is_ng = name == "ng-container" || name == "ng-template";
+        if directive.is_none() && !is_bound && !is_ng {
+            return None;
+        }
+
+        // `<ng-container>` and `<ng-template>` only render their content
+        let mut nodes = Vec::new();
+        if !is_ng {
+            nodes.push(if is_bound {
+                self.bound_tag(name.clone(), raw, self_closing)
+            } else {
+                // Only the directive is left out
+                let span = directive
+                    .as_ref()
+                    .map_or(tag_end..tag_end, |(.., span)| span.clone());
+                Node::Text(format!(
+                    "{}{}",
+                    self.src[start..span.start].trim_end(),
+                    &self.src[span.end..tag_end]
+                ))
+            });
+        }
+
+        let has_body = directive.is_some() || is_ng;
+        if !has_body && !self_closing {
+            self.opened = Some((name.to_ascii_lowercase(), start));
+        }
+        if has_body && !self_closing && !VOID_ELEMENTS.contains(&name.as_str()) {
+            let (body_end, close_end) = self.find_end_tag(&name).unwrap_or_else(|| {
+                self.error_at(start, format!("unclosed `<{}>`", name));
+                (self.src.len(), self.src.len())
+            });
+            nodes.extend(self.parse_region(tag_end, body_end));
+            if !is_ng {
+                nodes.push(Node::Text(self.src[body_end..close_end].to_string()));
+            }
+            self.byte_offset = close_end;
+        }
+
+        if name == "ng-template" {
+            // Only rendered where `*ngIf` names it
+            if let Some(reference) = reference {
+                self.templates.insert(reference, nodes);
+            }
+            return Some(Vec::new());
+        }
+
+        let Some((directive, value, _)) = directive else {
+            return Some(nodes);
+        };
+        let expr = value
+            .map(|(start, end, _)| self.src[start..end].trim())
+            .unwrap_or("");
+        let node = if directive == "*ngIf" {
+            self.parse_ng_if(start, expr, nodes)
+        } else {
+            self.parse_ng_for(start, expr, nodes)
+        };
+        Some(vec![node])
+    }
+
+    /// Drop `(event)`, `[(ngModel)]`, `#ref` and `i18n` attributes, or rename the ones in
+    /// `attribute_map`. Returns whether any attribute was changed.
+    fn rewrite_attributes(&self, raw: &mut Vec<RawAttribute>) -> bool {
+        let before = raw.len();
+        let mut renamed = false;
+        raw.retain_mut(|(name, ..)| {
+            let angular_only = name.starts_with(['(', '#'])
+                || name.starts_with("[(")
+                || name == "i18n"
+                || name.starts_with("i18n-");
+            if !angular_only {
+                return true;
+            }
+            match self.options.attribute_map.get(name.as_str()) {
+                Some(to) => {
+                    *name = to.clone();
+                    renamed = true;
+                    true
+                }
+                None => false,
+            }
+        });
+        renamed || raw.len() != before
+    }
+
+    /// A start tag with bindings, whose attributes are rendered one by one
+    fn bound_tag(&mut self, name: String, raw: Vec<RawAttribute>, self_closing: bool) -> Node {
+        let end = self.byte_offset;
+        // The static `class` and `style` are only merged when they have bindings
+        let binds = |prefix: &str, directive: &str| {
+            raw.iter().any(|(name, ..)| {
+                name.strip_prefix('[').is_some_and(|bound| {
+                    bound.starts_with(prefix) || bound == format!("{}]", directive)
+                })
+            })
+        };
+        let (merge_class, merge_style) = (binds("class", "ngClass"), binds("style", "ngStyle"));
+
+        let mut attributes = Vec::new();
+        for (name, value, _) in raw {
+            let Some(bound) = name.strip_prefix('[') else {
+                match name.as_str() {
+                    "class" if merge_class => {
+                        let value = value.map(|(start, end, _)| self.parse_region(start, end));
+                        class_bindings(&mut attributes)
+                            .insert(0, ClassBinding::Static(value.unwrap_or_default()));
+                    }
+                    "style" if merge_style => {
+                        let value = value.map(|(start, end, _)| self.parse_region(start, end));
+                        style_bindings(&mut attributes)
+                            .insert(0, StyleBinding::Static(value.unwrap_or_default()));
+                    }
+                    _ => {
+                        let value = value.map(|(start, end, quote)| {
+                            let mut nodes = self.parse_region(start, end);
+                            if quote != Some('"') {
+                                for node in &mut nodes {
+                                    if let Node::Text(text) = node {
+                                        *text = text.replace('"', "&quot;");
+                                    }
+                                }
+                            }
+                            nodes
+                        });
+                        attributes.push(Attribute::Static { name, value });
+                    }
+                }
+                continue;
+            };
+
+            let bound = bound.trim_end_matches(']');
+            let expr = value
+                .map(|(start, end, _)| self.src[start..end].trim())
+                .unwrap_or("null");
+            if let Some(class) = bound.strip_prefix("class.") {
+                class_bindings(&mut attributes).push(ClassBinding::Toggle {
+                    name: class.to_string(),
+                    cond: parse_bool_expr(expr),
+                });
+            } else if bound == "class" || bound == "ngClass" {
+                let bindings = class_bindings(&mut attributes);
+                match parse_object_literal(expr) {
+                    Some(entries) => bindings.extend(
+                        entries
+                            .into_iter()
+                            .map(|(name, cond)| ClassBinding::Toggle { name, cond }),
+                    ),
+                    None => bindings.push(ClassBinding::Expr(parse_bool_expr(expr))),
+                }
+            } else if let Some(property) = bound.strip_prefix("style.") {
+                style_bindings(&mut attributes).push(StyleBinding::Property {
+                    name: property.to_string(),
+                    value: parse_bool_expr(expr),
+                });
+            } else if bound == "style" || bound == "ngStyle" {
+                let bindings = style_bindings(&mut attributes);
+                match parse_object_literal(expr) {
+                    Some(entries) => bindings.extend(
+                        entries
+                            .into_iter()
+                            .map(|(name, value)| StyleBinding::Property { name, value }),
+                    ),
+                    None => bindings.push(StyleBinding::Expr(parse_bool_expr(expr))),
+                }
+            } else {
+                let name = bound.strip_prefix("attr.").unwrap_or(bound).to_string();
+                attributes.push(Attribute::Bound {
+                    boolean: BOOLEAN_ATTRIBUTES.contains(&name.as_str()),
+                    name,
+                    expr: parse_bool_expr(expr),
+                });
+            }
+        }
+        self.byte_offset = end;
+
+        Node::Tag(Tag {
+            name,
+            attributes,
+            self_closing,
+        })
+    }
+
+    /// `*ngIf="cond; else other"`, where `then` and `else` name `<ng-template #other>` blocks
+    fn parse_ng_if(&mut self, start: usize, expr: &str, body: Vec<Node>) -> Node {
+        let expr = expr
+            .replace(" then ", "; then ")
+            .replace(" else ", "; else ");
+        let mut clauses = split_top_level(&expr, ';').into_iter().map(str::trim);
+        let cond = clauses.next().unwrap_or("");
+        // `user as u` would need `u` bound in the body, which blocks do not do
+        if split_top_level(cond, ' ').contains(&"as")
+            && let Some((value, _)) = cond.rsplit_once(" as ")
+        {
+            self.error_at(
+                start,
+                format!(
+                    "`as` in `*ngIf` is not supported, use `{}` in the body",
+                    value.trim()
+                ),
+            );
+        }
+        let cond = parse_bool_expr(cond);
+
+        let (mut body, mut otherwise) = (body, None);
+        for clause in clauses.filter(|c| !c.is_empty()) {
+            match clause.split_once(' ') {
+                Some(("then", name)) => body = self.template_ref(start, name.trim()),
+                Some(("else", name)) => otherwise = Some(self.template_ref(start, name.trim())),
+                _ => self.error_at(start, format!("unknown `*ngIf` option `{}`", clause)),
+            }
+        }
+
+        Node::If(If {
+            conditions: vec![(cond, body)],
+            otherwise,
+        })
+    }
+
+    /// `*ngFor="let item of items; let i = index"`, parsed like `@for`
+    fn parse_ng_for(&mut self, start: usize, expr: &str, body: Vec<Node>) -> Node {
+        let mut clauses = split_top_level(expr, ';').into_iter().map(str::trim);
+        let first = clauses.next().unwrap_or("");
+        let mut header = first.strip_prefix("let ").unwrap_or(first).to_string();
+        for clause in clauses.filter(|c| !c.is_empty()) {
+            // `trackBy` names a method of the Angular component
+            if clause.starts_with("trackBy") {
+                continue;
+            }
+            match clause.split_once(" as ") {
+                Some((local, name)) => {
+                    header.push_str(&format!("; let {} = {}", name.trim(), local.trim()))
+                }
+                None => header.push_str(&format!("; {}", clause)),
+            }
+        }
+
+        let mut for_loop = self.parse_for_header(start, &header);
+        for_loop.body = body;
+        Node::Forloop(Box::new(for_loop))
+    }
+
+    /// A placeholder for `<ng-template #name>`, which can come later in the template
+    fn template_ref(&mut self, start: usize, name: &str) -> Vec<Node> {
+        self.template_refs.push((name.to_string(), start));
+        vec![Node::VariableBlock(vec![
+            TEMPLATE_REF.to_string(),
+            name.to_string(),
+        ])]
+    }
+
+    /// Fill in the `<ng-template>` blocks named by `*ngIf`
+    fn expand_template_refs(&mut self, nodes: &mut Vec<Node>) {
+        for (name, start) in std::mem::take(&mut self.template_refs) {
+            if !self.templates.contains_key(&name) {
+                self.error_at(start, format!("unknown template `#{}`", name));
+            }
+        }
+        expand_refs(nodes, &self.templates, 0);
+    }
+
+    /// Byte offsets of the matching `</name>` and just past it
+    fn find_end_tag(&self, name: &str) -> Option<(usize, usize)> {
+        let is_end_of_name =
+            |rest: &str| rest.starts_with(|c: char| c == '>' || c == '/' || c.is_whitespace());
+        let mut depth = 0;
+        let mut pos = self.byte_offset;
+        loop {
+            let i = pos + self.src[pos..].find('<')?;
+            let rest = &self.src[i + 1..];
+            if let Some(after) = rest.strip_prefix('/').and_then(|r| r.strip_prefix(name))
+                && is_end_of_name(after)
+            {
+                if depth == 0 {
+                    let close = i + self.src[i..].find('>')? + 1;
+                    return Some((i, close));
+                }
+                depth -= 1;
+            } else if let Some(after) = rest.strip_prefix(name)
+                && is_end_of_name(after)
+            {
+                depth += 1;
+            }
+            pos = i + 1;
+        }
+    }
+
+    /// The `{ ... }` of a block. `{~` trims the whitespace at the start of the body and `}~` the
+    /// whitespace after the block.
+    fn parse_block_body(&mut self) -> Vec<Node> {
+        self.expect_char('{');
+        if self.starts_with("~") {
+            self.byte_offset += 1;
+            self.skip_ws();
+        }
+        let body = self.parse_nodes(Some('}'));
+        if self.starts_with("~") {
+            self.byte_offset += 1;
+            self.skip_ws();
+        }
+        body
+    }
+
+    fn skip_comment(&mut self, open: &str, close: &str) {
+        let start = self.byte_offset;
+        let content = start + open.len();
+        self.byte_offset = match self.src[content..].find(close) {
+            Some(i) => content + i + close.len(),
+            None => {
+                self.error_at(start, format!("unclosed comment, expected `{}`", close));
+                self.src.len()
+            }
+        };
+    }
+
+    /// Whether the `<!--` at the current position starts a comment, and is not in an attribute
+    /// value or the content of an element like `<script>`
+    fn at_comment(&mut self) -> bool {
+        let pos = self.byte_offset;
+        if self.comments.at > pos {
+            self.comments = Comments::default();
+        }
+        let state = &mut self.comments;
+        let src = self.src;
+        let mut i = state.at;
+        let skip = |i: usize, close: &str| {
+            src[i..]
+                .find(close)
+                .map_or(src.len(), |j| i + j + close.len())
+        };
+
+        while i < pos {
+            let rest = &src[i..];
+            if let Some(name) = &state.text {
+                match rest.to_ascii_lowercase().find(&format!("</{}", name)) {
+                    Some(j) if i + j < pos => {
+                        i += j;
+                        state.text = None;
+                    }
+                    _ => i = pos,
+                }
+                continue;
+            }
+            if let Some(quote) = state.quote {
+                i = rest.find(quote).map_or(src.len(), |j| i + j + 1);
+                state.quote = None;
+                continue;
+            }
+            if rest.starts_with("{{!--") {
+                i = skip(i, "--}}");
+                continue;
+            }
+            if rest.starts_with("{{") {
+                i = skip(i, "}}");
+                continue;
+            }
+            let ch = rest.chars().next().unwrap_or_default();
+            if state.tag.is_some() {
+                match ch {
+                    '"' | '\'' => state.quote = Some(ch),
+                    '>' => {
+                        let self_closing = src[..i].ends_with('/');
+                        if let Some(name) = state.tag.take()
+                            && !self_closing
+                            && KEEP_COMMENTS.contains(&name.as_str())
+                        {
+                            state.text = Some(name);
+                        }
+                    }
+                    _ => {}
+                }
+                i += ch.len_utf8();
+                continue;
+            }
+            if rest.starts_with("<!--") {
+                i = skip(i, "-->");
+                continue;
+            }
+            if let Some(name) = rest.strip_prefix('<') {
+                let name: String = name
+                    .chars()
+                    .take_while(|c| c.is_ascii_alphanumeric() || *c == '-')
+                    .collect();
+                if name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                    i += 1 + name.len();
+                    state.tag = Some(name.to_ascii_lowercase());
+                    continue;
+                }
+            }
+            i += ch.len_utf8();
+        }
+
+        state.at = i;
+        i == pos && state.tag.is_none() && state.text.is_none()
+    }
+
+    /// Follow comments, start tags and end tags in HTML mode. Returns whether anything was read.
+    fn scan_markup(&mut self, markup: &mut Markup, text: &mut String) -> bool {
+        let start = self.byte_offset;
+        let Some(ch) = self.peek_char() else {
+            return false;
+        };
+
+        if markup.tag.is_some() {
+            match (markup.quote, ch) {
+                (Some(quote), _) if ch == quote => markup.quote = None,
+                (None, '"' | '\'') => markup.quote = Some(ch),
+                (None, '>') => {
+                    let self_closing = self.src[..start].ends_with('/');
+                    text.push('>');
+                    self.byte_offset += 1;
+                    if let Some((name, tag_start)) = markup.tag.take()
+                        && !self_closing
+                    {
+                        self.open_element(name, tag_start, markup, text);
+                    }
+                    return true;
+                }
+                _ => return false,
+            }
+            text.push(ch);
+            self.byte_offset += 1;
+            return true;
+        }
+
+        // Comments are text, braces and all
+        if self.starts_with("<!--") {
+            let end = match self.src[start..].find("-->") {
+                Some(i) => start + i + "-->".len(),
+                None => {
+                    self.error_at(start, "unclosed comment, expected `-->`");
+                    self.src.len()
+                }
+            };
+            text.push_str(&self.src[start..end]);
+            self.byte_offset = end;
+            return true;
+        }
+
+        let rest = &self.src[start..];
+        let (is_end, name_start) = match rest.strip_prefix("</") {
+            Some(_) => (true, 2),
+            None if ch == '<' => (false, 1),
+            None => return false,
+        };
+        let name: String = rest[name_start..]
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric() || *c == '-')
+            .collect();
+        if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            return false;
+        }
+        let name_end = start + name_start + name.len();
+        let name = name.to_ascii_lowercase();
+
+        if is_end {
+            let end = self.src[start..]
+                .find('>')
+                .map_or(self.src.len(), |i| start + i + 1);
+            text.push_str(&self.src[start..end]);
+            self.byte_offset = end;
+            self.close_element(&name, start, markup);
+        } else {
+            text.push_str(&self.src[start..name_end]);
+            self.byte_offset = name_end;
+            markup.tag = Some((name, start));
+        }
+        true
+    }
+
+    /// Track an element whose start tag was just read, and read the content of `<script>` and
+    /// `<style>` as text
+    fn open_element(&mut self, name: String, start: usize, markup: &mut Markup, text: &mut String) {
+        if VOID_ELEMENTS.contains(&name.as_str()) {
+            return;
+        }
+        if RAW_TEXT_ELEMENTS.contains(&name.as_str()) {
+            let end_tag = format!("</{}", name);
+            let content = &self.src[self.byte_offset..];
+            let len = content
+                .to_ascii_lowercase()
+                .find(&end_tag)
+                .unwrap_or(content.len());
+            text.push_str(&content[..len]);
+            self.byte_offset += len;
+        }
+        markup.open.push((name, start));
+    }
+
+    /// Close the element an end tag belongs to, reporting elements it leaves open
+    fn close_element(&mut self, name: &str, start: usize, markup: &mut Markup) {
+        let Some(i) = markup.open.iter().rposition(|(open, _)| open == name) else {
+            self.error_at(start, format!("unexpected `</{}>`", name));
+            return;
+        };
+        for (open, at) in markup.open.split_off(i).into_iter().skip(1) {
+            if !OPTIONAL_END_TAGS.contains(&open.as_str()) {
+                self.error_at(at, format!("unclosed `<{}>`", open));
+            }
+        }
+    }
+
+    /// Byte range of a quoted or unquoted value, and its quote
+    fn scan_attribute_value(&mut self) -> Option<(usize, usize, Option<char>)> {
+        let quote = self.peek_char().filter(|c| matches!(c, '"' | '\''));
+        let Some(quote) = quote else {
+            let start = self.byte_offset;
+            self.read_while(|c| !c.is_whitespace() && c != '>');
+            return Some((start, self.byte_offset, None));
+        };
+        let start = self.byte_offset + 1;
+        let len = self.src[start..].find(quote)?;
+        self.byte_offset = start + len + 1;
+        Some((start, start + len, Some(quote)))
+    }
+
+    /// Parse `start..end` on its own, like an attribute value or the content of an element
+    fn parse_region(&mut self, start: usize, end: usize) -> Vec<Node> {
+        let mut inner = Parser {
+            src: &self.src[..end],
+            byte_offset: start,
+            errors: Vec::new(),
+            options: self.options,
+            templates: std::mem::take(&mut self.templates),
+            template_refs: std::mem::take(&mut self.template_refs),
+            opened: None,
+            comments: Comments::default(),
+        };
+        let nodes = inner.parse_nodes(None);
+        self.errors.append(&mut inner.errors);
+        self.templates = inner.templates;
+        self.template_refs = inner.template_refs;
+        nodes
+    }
+
+    fn read_while(&mut self, pred: impl Fn(char) -> bool) -> String {
+        let rest = &self.src[self.byte_offset..];
+        let len = rest.find(|c| !pred(c)).unwrap_or(rest.len());
+        self.byte_offset += len;
+        rest[..len].to_string()
+    }
+
+    fn read_until_unbalanced(&mut self, end: char, start_pair: char) -> String {
+        let start_position = self.byte_offset;
+        let mut depth = 0;
+
+        let iter = self.src[self.byte_offset..].char_indices();
+        for (i, c) in iter {
+            if c == start_pair {
+                depth += 1;
+            } else if c == end {
+                if depth == 0 {
+                    let end_byte = self.byte_offset + i;
+                    let s = self.src[start_position..end_byte].to_string();
+                    // consume the end char
+                    self.byte_offset = end_byte + end.len_utf8();
+                    return s;
+                } else {
+                    depth -= 1;
+                }
+            }
+        }
+
+        let s = self.src[start_position..].to_string();
+        self.error_at(
+            start_position.saturating_sub(1),
+            format!("missing closing `{}`", end),
+        );
+        self.byte_offset = self.src.len();
+        s
+    }
+
+    #[inline]
+    fn skip_ws(&mut self) {
+        while let Some(c) = self.peek_char() {
+            if c.is_whitespace() {
+                self.byte_offset += c.len_utf8();
+            } else {
+                break;
+            }
+        }
+    }
+
+    #[inline]
+    fn expect_char(&mut self, expected: char) {
+        self.skip_ws();
+        if self.peek_char() == Some(expected) {
+            self.byte_offset += expected.len_utf8();
+        } else {
+            self.error_at(self.byte_offset, format!("expected `{}`", expected));
+        }
+    }
+
+    #[inline]
+    fn peek_char(&self) -> Option<char> {
+        self.src[self.byte_offset..].chars().next()
+    }
+
+    #[inline]
+    fn advance_one(&mut self) {
+        if let Some(ch) = self.peek_char() {
+            self.byte_offset += ch.len_utf8();
+        }
+    }
+
+    #[inline]
+    fn eof(&self) -> bool {
+        self.byte_offset >= self.src.len()
+    }
+
+    #[inline]
+    fn starts_with(&self, s: &str) -> bool {
+        self.src[self.byte_offset..].starts_with(s)
+    }
+
+    /// A block keyword on its own, so `info@for sale`, `info@for-sale.com` and `@iffy` stay text
+    fn at_keyword(&self, keyword: &str) -> bool {
+        let after_word = self.src[..self.byte_offset]
+            .chars()
+            .next_back()
+            .is_some_and(|c| c.is_alphanumeric() || c == '_');
+        !after_word
+            && self.src[self.byte_offset..]
+                .strip_prefix(keyword)
+                .is_some_and(|rest| {
+                    rest.chars()
+                        .next()
+                        .is_none_or(|c| c == '(' || c == '{' || c.is_whitespace())
+                })
+    }
+}
+
+/// Remove the whitespace before a trim marker
+fn trim_end(nodes: &mut [Node], text: &mut String) {
+    text.truncate(text.trim_end().len());
+    if text.is_empty()
+        && let Some(Node::Text(last)) = nodes.last_mut()
+    {
+        last.truncate(last.trim_end().len());
+    }
+}
+
+struct Collapse {
+    /// Open elements whose whitespace is kept
+    preserve: usize,
+    /// Drop the quotes of attribute values that don't need them
+    unquote: bool,
+}
+
+/// Remove whitespace between tags and blocks and turn other runs of whitespace into one space,
+/// like Angular's `preserveWhitespaces: false`
+fn collapse_whitespace(nodes: &mut Vec<Node>, state: &mut Collapse) {
+    // Interpolations are part of the text around them
+    let is_text = |node: Option<&Node>| {
+        matches!(
+            node,
+            Some(Node::Text(_) | Node::VariableBlock(_) | Node::Call(_))
+        )
+    };
+    for i in 0..nodes.len() {
+        let (before, after) = (
+            i.checked_sub(1).and_then(|i| nodes.get(i)),
+            nodes.get(i + 1),
+        );
+        let (text_before, text_after) = (is_text(before), is_text(after));
+        match &mut nodes[i] {
+            Node::Text(text) => *text = collapse_text(text, text_before, text_after, state),
+            Node::Tag(tag)
+                if PRESERVE_WHITESPACE.contains(&tag.name.as_str()) && !tag.self_closing =>
+            {
+                state.preserve += 1;
+            }
+            node => {
+                for body in blocks_mut(node) {
+                    collapse_whitespace(body, state);
+                }
+            }
+        }
+    }
+    nodes.retain(|node| !matches!(node, Node::Text(text) if text.is_empty()));
+}
+
+fn collapse_text(text: &str, text_before: bool, text_after: bool, state: &mut Collapse) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    // Only start tags that begin in this text, a value after an interpolation may be split
+    let mut in_tag = false;
+    while let Some(ch) = rest.chars().next() {
+        if state.unquote
+            && in_tag
+            && state.preserve == 0
+            && ch == '='
+            && let Some(value) = unquoted_value(&rest[1..])
+        {
+            out.push('=');
+            out.push_str(value);
+            rest = &rest[value.len() + 3..];
+            continue;
+        }
+        match ch {
+            '<' => in_tag = rest[1..].starts_with(|c: char| c.is_ascii_alphabetic()),
+            '>' => in_tag = false,
+            _ => {}
+        }
+        if ch == '<' {
+            let tag = &rest[1..];
+            let (tag, closing) = match tag.strip_prefix('/') {
+                Some(tag) => (tag, true),
+                None => (tag, false),
+            };
+            let name_len = tag
+                .find(|c: char| !c.is_ascii_alphanumeric())
+                .unwrap_or(tag.len());
+            if PRESERVE_WHITESPACE.contains(&tag[..name_len].to_ascii_lowercase().as_str()) {
+                if closing {
+                    state.preserve = state.preserve.saturating_sub(1);
+                } else {
+                    state.preserve += 1;
+                }
+            }
+        }
+        if state.preserve > 0 || !ch.is_whitespace() {
+            out.push(ch);
+            rest = &rest[ch.len_utf8()..];
+            continue;
+        }
+
+        let len = rest
+            .find(|c: char| !c.is_whitespace())
+            .unwrap_or(rest.len());
+        let after = &rest[len..];
+        // Between tags, or between a tag and a block
+        let starts_node = out.is_empty() && !text_before || out.ends_with('>');
+        let ends_node = after.is_empty() && !text_after || after.starts_with('<');
+        if !(starts_node && ends_node) {
+            out.push(' ');
+        }
+        rest = after;
+    }
+    out
+}
+
+/// The value of a quoted attribute value at the start of `rest` that is the same without quotes
+fn unquoted_value(rest: &str) -> Option<&str> {
+    let quote = rest.chars().next().filter(|c| matches!(c, '"' | '\''))?;
+    let value = &rest[1..1 + rest[1..].find(quote)?];
+    let needs_quotes = value.is_empty()
+        // `<a href=/x/>` would end the value with the `/`
+        || value.ends_with('/')
+        || value.contains(|c: char| c.is_whitespace() || matches!(c, '"' | '\'' | '=' | '<' | '>' | '`'));
+    (!needs_quotes).then_some(value)
+}
+
+/// Replace the placeholders of `*ngIf` with the `<ng-template>` blocks they name
+fn expand_refs(nodes: &mut Vec<Node>, templates: &HashMap<String, Vec<Node>>, depth: usize) {
+    let is_ref = |node: &Node| matches!(node, Node::VariableBlock(path) if path.len() == 2 && path[0] == TEMPLATE_REF);
+    if nodes.iter().any(is_ref) {
+        *nodes = std::mem::take(nodes)
+            .into_iter()
+            .flat_map(|node| match node {
+                Node::VariableBlock(path) if is_ref(&Node::VariableBlock(path.clone())) => {
+                    let mut body = templates.get(&path[1]).cloned().unwrap_or_default();
+                    if depth < MAX_TEMPLATE_DEPTH {
+                        expand_refs(&mut body, templates, depth + 1);
+                    } else {
+                        body.clear();
+                    }
+                    body
+                }
+                node => vec![node],
+            })
+            .collect();
+    }
+
+    for body in nodes.iter_mut().flat_map(blocks_mut) {
+        expand_refs(body, templates, depth);
+    }
+}
+
+/// The merged `class` of a tag, added where the first class attribute or binding is
+fn class_bindings(attributes: &mut Vec<Attribute>) -> &mut Vec<ClassBinding> {
+    let i = match attributes
+        .iter()
+        .position(|a| matches!(a, Attribute::Class(_)))
+    {
+        Some(i) => i,
+        None => {
+            attributes.push(Attribute::Class(Vec::new()));
+            attributes.len() - 1
+        }
+    };
+    match &mut attributes[i] {
+        Attribute::Class(bindings) => bindings,
+        _ => unreachable!("found above"),
+    }
+}
+
+/// The merged `style` of a tag, added where the first style attribute or binding is
+fn style_bindings(attributes: &mut Vec<Attribute>) -> &mut Vec<StyleBinding> {
+    let i = match attributes
+        .iter()
+        .position(|a| matches!(a, Attribute::Style(_)))
+    {
+        Some(i) => i,
+        None => {
+            attributes.push(Attribute::Style(Vec::new()));
+            attributes.len() - 1
+        }
+    };
+    match &mut attributes[i] {
+        Attribute::Style(bindings) => bindings,
+        _ => unreachable!("found above"),
+    }
+}
+
+/// `{'active': isActive, disabled: !enabled}`, as in `[ngClass]` and `[ngStyle]`
+fn parse_object_literal(expr: &str) -> Option<Vec<(String, Condition)>> {
+    let inner = expr.strip_prefix('{')?.strip_suffix('}')?;
+    split_top_level(inner, ',')
+        .into_iter()
+        .filter(|entry| !entry.trim().is_empty())
+        .map(|entry| {
+            let key = split_top_level(entry, ':').into_iter().next()?;
+            // Without a ':' the key is the whole entry
+            let value = entry.get(key.len() + 1..)?;
+            let key = key.trim().trim_matches(['\'', '"']);
+            Some((key.to_string(), parse_bool_expr(value.trim())))
+        })
+        .collect()
+}
+
+/// Split on `sep` outside of quotes and brackets
+fn split_top_level(s: &str, sep: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let (mut depth, mut quote, mut start) = (0usize, None, 0);
+    for (i, c) in s.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '(' | '[' | '{') => depth += 1,
+            (None, ')' | ']' | '}') => depth = depth.saturating_sub(1),
+            (None, c) if c == sep && depth == 0 => {
+                parts.push(&s[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+/// `item in items` or `item of items`
+fn parse_for_expression(expr: &str) -> (String, String) {
+    let trimmed = expr.trim();
+    let split = [" in ", " of "]
+        .into_iter()
+        .filter_map(|sep| trimmed.find(sep).map(|i| (i, sep.len())))
+        .min();
+    let Some((i, len)) = split else {
+        return (trimmed.to_string(), String::new());
+    };
+    let value = trimmed[..i].trim().to_string();
+    let container = trimmed[i + len..].trim().to_string();
+    (value, container)
+}
+
+fn parse_kv_pairs(s: &str) -> Vec<(String, LocalValue)> {
+    let normalized = s.replace(';', " ");
+    let tokens = normalized.split_whitespace();
+
+    tokens
+        .filter_map(|pair| {
+            let mut kv = pair.splitn(2, '=').map(|x| x.trim());
+            let k = kv.next()?;
+            let v = kv.next()?;
+
+            if (v.starts_with('"') && v.ends_with('"'))
+                || (v.starts_with('\'') && v.ends_with('\''))
+            {
+                let inner = &v[1..v.len() - 1];
+                return Some((
+                    k.to_string(),
+                    LocalValue::Literal(Value::String(inner.to_string())),
+                ));
+            }
+
+            if v == "true" {
+                return Some((k.to_string(), LocalValue::Literal(Value::Bool(true))));
+            }
+
+            if v == "false" {
+                return Some((k.to_string(), LocalValue::Literal(Value::Bool(false))));
+            }
+
+            if v == "null" {
+                return Some((k.to_string(), LocalValue::Literal(Value::Null)));
+            }
+
+            if let Ok(i) = v.parse::<i64>() {
+                return Some((k.to_string(), LocalValue::Literal(Value::Number(i.into()))));
+            }
+
+            if let Ok(f) = v.parse::<f64>() {
+                return Some((k.to_string(), LocalValue::Literal(serde_json::json!(f))));
+            }
+
+            Some((k.to_string(), LocalValue::Path(parse_variable_path(v))))
+        })
+        .collect()
+}
+
+/// The content of a quoted string without quotes of the same kind inside
+fn string_literal(expr: &str) -> Option<&str> {
+    let quote = expr.chars().next().filter(|c| matches!(c, '"' | '\''))?;
+    let inner = expr.strip_prefix(quote)?.strip_suffix(quote)?;
+    (!inner.contains(quote)).then_some(inner)
+}
+
+fn parse_variable_path(expr: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut in_brackets = false;
+
+    for c in expr.chars() {
+        match c {
+            '[' => {
+                if !current.is_empty() {
+                    parts.push(current.clone());
+                    current.clear();
+                }
+                in_brackets = true;
+            }
+            ']' => {
+                if in_brackets {
+                    parts.push(current.clone());
+                    current.clear();
+                    in_brackets = false;
+                }
+            }
+            '"' | '\'' => continue,
+            '.' if !in_brackets => {
+                if !current.is_empty() {
+                    parts.push(current.clone());
+                    current.clear();
+                }
+            }
+            _ => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        parts.push(current);
+    }
+    parts
+}
+
+#[derive(Debug, Clone)]
+enum Token {
+    Ident(String),
+    And,
+    Or,
+    Not,
+    Eq,
+    Ne,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    In,
+    Contains,
+    StartsWith,
+    EndsWith,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    LParen,
+    RParen,
+    Comma,
+    Assign,
+}
+
+fn parse_unary(cur: &mut Cursor) -> Condition {
+    match cur.peek() {
+        Some(Token::Not) => {
+            cur.next(); // consume '!'
+            let inner = parse_unary(cur); // right-associative
+            Condition::Not(Box::new(inner))
+        }
+        _ => parse_factor(cur),
+    }
+}
+
+fn tokenize_bool(s: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut cur = String::new();
+
+    let push_cur = |cur: &mut String, tokens: &mut Vec<Token>| {
+        if cur.is_empty() {
+            return;
+        }
+        let w = cur.trim().to_string();
+        cur.clear();
+        match w.as_str() {
+            "and" | "&&" => tokens.push(Token::And),
+            "or" | "||" => tokens.push(Token::Or),
+            "not" => tokens.push(Token::Not),
+            "in" => tokens.push(Token::In),
+            "contains" => tokens.push(Token::Contains),
+            "startsWith" => tokens.push(Token::StartsWith),
+            "endsWith" => tokens.push(Token::EndsWith),
+            _ => tokens.push(Token::Ident(w)),
+        }
+    };
+
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' | '\'' => {
+                // Quoted strings are one token, quotes included
+                cur.push(c);
+                for q in chars.by_ref() {
+                    cur.push(q);
+                    if q == c {
+                        break;
+                    }
+                }
+            }
+            ',' => {
+                push_cur(&mut cur, &mut tokens);
+                tokens.push(Token::Comma);
+            }
+            '+' | '*' | '/' | '%' => {
+                push_cur(&mut cur, &mut tokens);
+                tokens.push(match c {
+                    '+' => Token::Plus,
+                    '*' => Token::Star,
+                    '/' => Token::Slash,
+                    _ => Token::Percent,
+                });
+            }
+            // A minus after an operand subtracts, otherwise it belongs to a number or name
+            '-' if cur.is_empty()
+                && matches!(tokens.last(), Some(Token::Ident(_) | Token::RParen)) =>
+            {
+                tokens.push(Token::Minus);
+            }
+            '(' => {
+                push_cur(&mut cur, &mut tokens);
+                tokens.push(Token::LParen);
+            }
+            ')' => {
+                push_cur(&mut cur, &mut tokens);
+                tokens.push(Token::RParen);
+            }
+            '=' => {
+                push_cur(&mut cur, &mut tokens);
+                if chars.peek() == Some(&'=') {
+                    chars.next();
+                    tokens.push(Token::Eq);
+                } else {
+                    tokens.push(Token::Assign);
+                }
+            }
+            '!' => {
+                push_cur(&mut cur, &mut tokens);
+                if chars.peek() == Some(&'=') {
+                    chars.next();
+                    tokens.push(Token::Ne);
+                } else {
+                    tokens.push(Token::Not);
+                }
+            }
+            '<' => {
+                push_cur(&mut cur, &mut tokens);
+                if chars.peek() == Some(&'=') {
+                    chars.next();
+                    tokens.push(Token::Le);
+                } else {
+                    tokens.push(Token::Lt);
+                }
+            }
+            '>' => {
+                push_cur(&mut cur, &mut tokens);
+                if chars.peek() == Some(&'=') {
+                    chars.next();
+                    tokens.push(Token::Ge);
+                } else {
+                    tokens.push(Token::Gt);
+                }
+            }
+
+            c if c.is_whitespace() => {
+                push_cur(&mut cur, &mut tokens);
+            }
+            _ => cur.push(c),
+        }
+    }
+    push_cur(&mut cur, &mut tokens);
+    tokens
+}
+
+struct Cursor {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Cursor {
+    fn new(tokens: Vec<Token>) -> Self {
+        Self {
+            tokens,
+            position: 0,
+        }
+    }
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+    fn peek_second(&self) -> Option<&Token> {
+        self.tokens.get(self.position + 1)
+    }
+    fn next(&mut self) -> Option<Token> {
+        let t = self.tokens.get(self.position).cloned();
+        if t.is_some() {
+            self.position += 1;
+        }
+        t
+    }
+}
+
+fn parse_bool_expr(s: &str) -> Condition {
+    let tokens = tokenize_bool(s);
+    let mut cur = Cursor::new(tokens);
+    parse_expr(&mut cur)
+}
+
+fn parse_expr(cur: &mut Cursor) -> Condition {
+    let left = parse_term(cur);
+    let mut parts = vec![left];
+
+    while let Some(Token::Or) = cur.peek() {
+        cur.next();
+        let rhs = parse_term(cur);
+        parts.push(rhs);
+    }
+
+    if parts.len() == 1 {
+        parts[0].clone()
+    } else {
+        Condition::Or(parts)
+    }
+}
+
+fn parse_term(cur: &mut Cursor) -> Condition {
+    let left = parse_unary(cur);
+    let mut parts = vec![left];
+
+    while let Some(Token::And) = cur.peek() {
+        cur.next();
+        let rhs = parse_unary(cur);
+        parts.push(rhs);
+    }
+
+    if parts.len() == 1 {
+        parts[0].clone()
+    } else {
+        Condition::And(parts)
+    }
+}
+
+fn parse_factor(cur: &mut Cursor) -> Condition {
+    match cur.peek() {
+        Some(Token::LParen) => {
+            cur.next(); // '('
+            let inner = parse_expr(cur);
+            if let Some(Token::RParen) = cur.peek() {
+                cur.next(); // ')'
+            }
+            inner
+        }
+        Some(Token::Ident(_)) => {
+            let left = parse_operand(cur);
+
+            // 'a not in b'
+            let negate = matches!(
+                (cur.peek(), cur.peek_second()),
+                (Some(Token::Not), Some(Token::In))
+            );
+            if negate {
+                cur.next(); // consume 'not'
+            }
+
+            if let Some(op_tok) = cur.peek()
+                && let Some(op) = parse_compare_op(op_tok)
+            {
+                cur.next(); // consume operator
+                let right = parse_operand(cur);
+                let compare = Condition::Compare { left, op, right };
+                return if negate {
+                    Condition::Not(Box::new(compare))
+                } else {
+                    compare
+                };
+            }
+            match left {
+                Operand::Literal(value) => Condition::Literal(value),
+                Operand::Path(path) => Condition::Path(path),
+                operand => Condition::Operand(operand),
+            }
+        }
+        _ => Condition::Literal(Value::Bool(false)),
+    }
+}
+
+/// An operand on its own, like a `track` expression
+fn parse_operand_expr(s: &str) -> Operand {
+    let mut cur = Cursor::new(tokenize_bool(s));
+    parse_operand(&mut cur)
+}
+
+/// `name(args, key=value)` in an interpolation
+fn parse_call_expr(s: &str) -> Option<Call> {
+    let mut cur = Cursor::new(tokenize_bool(s));
+    let Some(Token::Ident(name)) = cur.next() else {
+        return None;
+    };
+    if !matches!(cur.peek(), Some(Token::LParen)) {
+        return None;
+    }
+    let call = parse_call(name, &mut cur);
+    cur.peek().is_none().then_some(call)
+}
+
+fn parse_call(name: String, cur: &mut Cursor) -> Call {
+    cur.next(); // '('
+    let mut call = Call {
+        name,
+        args: Vec::new(),
+        named: Vec::new(),
+    };
+
+    loop {
+        match (cur.peek(), cur.peek_second()) {
+            (None, _) => break,
+            (Some(Token::RParen), _) => {
+                cur.next();
+                break;
+            }
+            (Some(Token::Comma), _) => {
+                cur.next();
+            }
+            (Some(Token::Ident(key)), Some(Token::Assign)) => {
+                let key = key.clone();
+                cur.next(); // key
+                cur.next(); // '='
+                call.named.push((key, parse_operand(cur)));
+            }
+            _ => call.args.push(parse_operand(cur)),
+        }
+    }
+    call
+}
+
+/// An operand with `+ - * / %`, multiplication binding tighter
+fn parse_operand(cur: &mut Cursor) -> Operand {
+    let mut left = parse_product(cur);
+    loop {
+        let op = match cur.peek() {
+            Some(Token::Plus) => ArithOp::Add,
+            Some(Token::Minus) => ArithOp::Sub,
+            _ => return left,
+        };
+        cur.next();
+        let right = parse_product(cur);
+        left = Operand::Arith {
+            left: Box::new(left),
+            op,
+            right: Box::new(right),
+        };
+    }
+}
+
+fn parse_product(cur: &mut Cursor) -> Operand {
+    let mut left = parse_primary(cur);
+    loop {
+        let op = match cur.peek() {
+            Some(Token::Star) => ArithOp::Mul,
+            Some(Token::Slash) => ArithOp::Div,
+            Some(Token::Percent) => ArithOp::Rem,
+            _ => return left,
+        };
+        cur.next();
+        let right = parse_primary(cur);
+        left = Operand::Arith {
+            left: Box::new(left),
+            op,
+            right: Box::new(right),
+        };
+    }
+}
+
+fn parse_primary(cur: &mut Cursor) -> Operand {
+    match cur.next() {
+        Some(Token::LParen) => {
+            let inner = parse_operand(cur);
+            if let Some(Token::RParen) = cur.peek() {
+                cur.next();
+            }
+            inner
+        }
+        Some(Token::Ident(s)) => {
+            let t = s.as_str();
+            let is_quoted = (t.starts_with('"') && t.ends_with('"'))
+                || (t.starts_with('\'') && t.ends_with('\''));
+            let is_bool = t == "true" || t == "false";
+            let is_int = t.parse::<i64>().is_ok();
+            let is_float = t.parse::<f64>().is_ok();
+
+            if is_quoted || is_bool || is_int || is_float {
+                Operand::Literal(parse_literal(Some(Token::Ident(s))))
+            } else if let Some(Token::LParen) = cur.peek() {
+                Operand::Call(parse_call(s, cur))
+            } else {
+                Operand::Path(parse_variable_path(&s))
+            }
+        }
+        other => Operand::Literal(parse_literal(other)),
+    }
+}
+
+fn parse_compare_op(tok: &Token) -> Option<CompareOp> {
+    match tok {
+        Token::Eq => Some(CompareOp::Eq),
+        Token::Ne => Some(CompareOp::Ne),
+        Token::Lt => Some(CompareOp::Lt),
+        Token::Gt => Some(CompareOp::Gt),
+        Token::Le => Some(CompareOp::Le),
+        Token::Ge => Some(CompareOp::Ge),
+        Token::In => Some(CompareOp::In),
+        Token::Contains => Some(CompareOp::Contains),
+        Token::StartsWith => Some(CompareOp::StartsWith),
+        Token::EndsWith => Some(CompareOp::EndsWith),
+        _ => None,
+    }
+}
+
+fn parse_literal(tok: Option<Token>) -> Value {
+    match tok {
+        Some(Token::Ident(s)) => {
+            if s == "true" {
+                Value::Bool(true)
+            } else if s == "false" {
+                Value::Bool(false)
+            } else if let Ok(i) = s.parse::<i64>() {
+                Value::Number(i.into())
+            } else if let Ok(f) = s.parse::<f64>() {
+                serde_json::json!(f)
+            } else if s.len() >= 2
+                && ((s.starts_with('"') && s.ends_with('"'))
+                    || (s.starts_with('\'') && s.ends_with('\'')))
+            {
+                Value::String(s[1..s.len() - 1].to_string())
+            } else {
+                Value::String(s)
+            }
+        }
+        _ => Value::Null,
+    }
+}
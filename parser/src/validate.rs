@@ -0,0 +1,69 @@
+use crate::nodes::{Include, Node};
+use crate::parser::ParseError;
+use crate::visit::{Visit, walk_include};
+
+/// A problem found in a template while validating it
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub template: String,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub message: String,
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => {
+                write!(f, "{}:{}:{}: {}", self.template, line, column, self.message)
+            }
+            _ => write!(f, "{}: {}", self.template, self.message),
+        }
+    }
+}
+
+impl std::error::Error for Diagnostic {}
+
+/// Collect parse errors and `@defer` targets that `exists` does not know about
+pub fn diagnose(
+    template: &str,
+    nodes: &[Node],
+    errors: &[ParseError],
+    exists: &dyn Fn(&str) -> bool,
+) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = errors
+        .iter()
+        .map(|e| Diagnostic {
+            template: template.to_string(),
+            line: Some(e.line),
+            column: Some(e.column),
+            message: e.message.clone(),
+        })
+        .collect();
+
+    let mut includes = Includes(Vec::new());
+    includes.visit_nodes(nodes);
+
+    for path in includes.0 {
+        if !path.is_empty() && !exists(path) {
+            diagnostics.push(Diagnostic {
+                template: template.to_string(),
+                line: None,
+                column: None,
+                message: format!("`@defer` target `{}` does not exist", path),
+            });
+        }
+    }
+
+    diagnostics
+}
+
+/// `@defer` targets, in order
+struct Includes<'a>(Vec<&'a str>);
+
+impl<'a> Visit<'a> for Includes<'a> {
+    fn visit_include(&mut self, include: &'a Include) {
+        self.0.push(&include.path);
+        walk_include(self, include);
+    }
+}
@@ -0,0 +1,210 @@
+//! Walking the node tree, so code that looks for paths, calls or `@defer` targets does not have
+//! to follow every kind of node itself.
+//!
+//! Implement the `visit_*` methods for what you are looking for. The defaults walk into the
+//! children with the `walk_*` functions, which an override calls to keep walking.
+
+use crate::nodes::{
+    Attribute, Call, ClassBinding, Condition, ForLoop, If, Include, Iterable, LocalValue, Node,
+    Operand, StyleBinding, Tag,
+};
+
+pub trait Visit<'a> {
+    fn visit_nodes(&mut self, nodes: &'a [Node]) {
+        for node in nodes {
+            self.visit_node(node);
+        }
+    }
+
+    fn visit_node(&mut self, node: &'a Node) {
+        walk_node(self, node);
+    }
+
+    /// A path looked up in the context, like `user.name`
+    fn visit_path(&mut self, _path: &'a [String]) {}
+
+    fn visit_call(&mut self, call: &'a Call) {
+        walk_call(self, call);
+    }
+
+    fn visit_include(&mut self, include: &'a Include) {
+        walk_include(self, include);
+    }
+
+    fn visit_condition(&mut self, cond: &'a Condition) {
+        walk_condition(self, cond);
+    }
+
+    fn visit_operand(&mut self, operand: &'a Operand) {
+        walk_operand(self, operand);
+    }
+}
+
+pub fn walk_node<'a, V: Visit<'a> + ?Sized>(visitor: &mut V, node: &'a Node) {
+    match node {
+        Node::Text(_) | Node::ContentPlaceholder => {}
+        Node::VariableBlock(path) => visitor.visit_path(path),
+        Node::Call(call) => visitor.visit_call(call),
+        Node::Tag(tag) => walk_tag(visitor, tag),
+        Node::If(If {
+            conditions,
+            otherwise,
+        }) => {
+            for (cond, body) in conditions {
+                visitor.visit_condition(cond);
+                visitor.visit_nodes(body);
+            }
+            if let Some(body) = otherwise {
+                visitor.visit_nodes(body);
+            }
+        }
+        Node::Forloop(for_loop) => {
+            let ForLoop {
+                container,
+                filter,
+                limit,
+                track,
+                body,
+                ..
+            } = for_loop.as_ref();
+            match container {
+                Iterable::Path(path) => visitor.visit_path(path),
+                Iterable::Range { start, end, step } => {
+                    for operand in [Some(start), Some(end), step.as_ref()]
+                        .into_iter()
+                        .flatten()
+                    {
+                        visitor.visit_operand(operand);
+                    }
+                }
+            }
+            if let Some(filter) = filter {
+                visitor.visit_condition(filter);
+            }
+            for operand in [limit, track].into_iter().flatten() {
+                visitor.visit_operand(operand);
+            }
+            visitor.visit_nodes(body);
+        }
+        Node::Include(include) => visitor.visit_include(include),
+    }
+}
+
+fn walk_tag<'a, V: Visit<'a> + ?Sized>(visitor: &mut V, tag: &'a Tag) {
+    for attribute in &tag.attributes {
+        match attribute {
+            Attribute::Static {
+                value: Some(value), ..
+            } => visitor.visit_nodes(value),
+            Attribute::Static { value: None, .. } => {}
+            Attribute::Bound { expr, .. } => visitor.visit_condition(expr),
+            Attribute::Class(bindings) => {
+                for binding in bindings {
+                    match binding {
+                        ClassBinding::Static(value) => visitor.visit_nodes(value),
+                        ClassBinding::Toggle { cond: expr, .. } | ClassBinding::Expr(expr) => {
+                            visitor.visit_condition(expr)
+                        }
+                    }
+                }
+            }
+            Attribute::Style(bindings) => {
+                for binding in bindings {
+                    match binding {
+                        StyleBinding::Static(value) => visitor.visit_nodes(value),
+                        StyleBinding::Property { value: expr, .. } | StyleBinding::Expr(expr) => {
+                            visitor.visit_condition(expr)
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub fn walk_call<'a, V: Visit<'a> + ?Sized>(visitor: &mut V, call: &'a Call) {
+    for arg in call
+        .args
+        .iter()
+        .chain(call.named.iter().map(|(_, arg)| arg))
+    {
+        visitor.visit_operand(arg);
+    }
+}
+
+/// The local context, the body and the `@placeholder`, `@loading` and `@error` blocks.
+/// The partial itself is another template.
+pub fn walk_include<'a, V: Visit<'a> + ?Sized>(visitor: &mut V, include: &'a Include) {
+    for (_, local_val) in &include.local_ctx {
+        if let LocalValue::Path(path) = local_val {
+            visitor.visit_path(path);
+        }
+    }
+    visitor.visit_nodes(&include.body);
+    for block in [&include.placeholder, &include.loading, &include.error]
+        .into_iter()
+        .flatten()
+    {
+        visitor.visit_nodes(block);
+    }
+}
+
+pub fn walk_condition<'a, V: Visit<'a> + ?Sized>(visitor: &mut V, cond: &'a Condition) {
+    match cond {
+        Condition::Literal(_) => {}
+        Condition::Path(path) => visitor.visit_path(path),
+        Condition::Operand(operand) => visitor.visit_operand(operand),
+        Condition::And(conds) | Condition::Or(conds) => {
+            for c in conds {
+                visitor.visit_condition(c);
+            }
+        }
+        Condition::Not(inner) => visitor.visit_condition(inner),
+        Condition::Compare { left, right, .. } => {
+            visitor.visit_operand(left);
+            visitor.visit_operand(right);
+        }
+    }
+}
+
+pub fn walk_operand<'a, V: Visit<'a> + ?Sized>(visitor: &mut V, operand: &'a Operand) {
+    match operand {
+        Operand::Path(path) => visitor.visit_path(path),
+        Operand::Call(call) => visitor.visit_call(call),
+        Operand::Arith { left, right, .. } => {
+            visitor.visit_operand(left);
+            visitor.visit_operand(right);
+        }
+        Operand::Literal(_) => {}
+    }
+}
+
+/// The node lists rendered as part of a block: the bodies of `@if`, `@for` and `@defer` and the
+/// `@placeholder`, `@loading` and `@error` blocks. Attribute values are not blocks.
+pub fn blocks_mut(node: &mut Node) -> Vec<&mut Vec<Node>> {
+    match node {
+        Node::Text(_)
+        | Node::VariableBlock(_)
+        | Node::Call(_)
+        | Node::Tag(_)
+        | Node::ContentPlaceholder => Vec::new(),
+        Node::If(If {
+            conditions,
+            otherwise,
+        }) => conditions
+            .iter_mut()
+            .map(|(_, body)| body)
+            .chain(otherwise)
+            .collect(),
+        Node::Forloop(for_loop) => vec![&mut for_loop.body],
+        Node::Include(Include {
+            body,
+            placeholder,
+            loading,
+            error,
+            ..
+        }) => std::iter::once(body)
+            .chain([placeholder, loading, error].into_iter().flatten())
+            .collect(),
+    }
+}
@@ -0,0 +1,143 @@
+//! How conditions compare values and decide truthiness.
+//!
+//! `false`, `0`, `""`, `null`, `[]`, `{}` and missing values are falsy, everything else is
+//! truthy. The same rules apply to `@if (value)`, `!`, `&&`, `||` and literals like `@if (0)`.
+//!
+//! In comparisons a missing value is `null`. Numbers are compared exactly when both are
+//! integers, so large ids don't lose precision through `f64`. Strings are ordered by bytes.
+//!
+//! With [`Comparison::Strict`], the default, values of different types are never equal and
+//! only numbers and strings can be ordered. With [`Comparison::Loose`], comparisons coerce
+//! like JavaScript's `==` and `<`: numeric strings and booleans compare as numbers, so
+//! `"5" < 6` and `true == 1` hold. `null` is only equal to `null`, and arrays and objects
+//! are never coerced.
+
+use std::cmp::Ordering;
+
+use serde_json::{Number, Value};
+
+use crate::nodes::CompareOp;
+
+/// How values of different types are compared, set with `Templates::set_comparison`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Comparison {
+    /// Values of different types are never equal and can't be ordered
+    #[default]
+    Strict,
+    /// Numeric strings and booleans are coerced to numbers, like JavaScript
+    Loose,
+}
+
+pub fn is_truthy(val: &Value) -> bool {
+    match val {
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64().is_some_and(|f| f != 0.0),
+        Value::String(s) => !s.is_empty(),
+        Value::Null => false,
+        Value::Array(a) => !a.is_empty(),
+        Value::Object(o) => !o.is_empty(),
+    }
+}
+
+pub fn compare_values(left: &Value, op: &CompareOp, right: &Value, mode: Comparison) -> bool {
+    match op {
+        CompareOp::Eq => equals(left, right, mode),
+        CompareOp::Ne => !equals(left, right, mode),
+        CompareOp::Lt => order(left, right, mode) == Some(Ordering::Less),
+        CompareOp::Gt => order(left, right, mode) == Some(Ordering::Greater),
+        CompareOp::Le => order(left, right, mode).is_some_and(Ordering::is_le),
+        CompareOp::Ge => order(left, right, mode).is_some_and(Ordering::is_ge),
+        CompareOp::In => contains(right, left, mode),
+        CompareOp::Contains => contains(left, right, mode),
+        CompareOp::StartsWith | CompareOp::EndsWith => {
+            let (Value::String(ls), Value::String(rs)) = (left, right) else {
+                return false;
+            };
+            match op {
+                CompareOp::StartsWith => ls.starts_with(rs.as_str()),
+                _ => ls.ends_with(rs.as_str()),
+            }
+        }
+    }
+}
+
+fn equals(left: &Value, right: &Value, mode: Comparison) -> bool {
+    match (left, right) {
+        (Value::Number(ln), Value::Number(rn)) => compare_numbers(ln, rn) == Some(Ordering::Equal),
+        (Value::Array(_) | Value::Object(_), _) | (_, Value::Array(_) | Value::Object(_)) => {
+            left == right
+        }
+        (Value::Null, _) | (_, Value::Null) => left == right,
+        _ if same_type(left, right) => left == right,
+        _ => match mode {
+            Comparison::Strict => false,
+            Comparison::Loose => match (coerce_number(left), coerce_number(right)) {
+                (Some(ln), Some(rn)) => compare_numbers(&ln, &rn) == Some(Ordering::Equal),
+                _ => false,
+            },
+        },
+    }
+}
+
+fn order(left: &Value, right: &Value, mode: Comparison) -> Option<Ordering> {
+    match (left, right) {
+        (Value::String(ls), Value::String(rs)) => Some(ls.cmp(rs)),
+        (Value::Number(ln), Value::Number(rn)) => compare_numbers(ln, rn),
+        _ => match mode {
+            Comparison::Strict => None,
+            Comparison::Loose => compare_numbers(&coerce_number(left)?, &coerce_number(right)?),
+        },
+    }
+}
+
+/// Integers are compared exactly, floats through `f64`
+fn compare_numbers(left: &Number, right: &Number) -> Option<Ordering> {
+    if let (Some(l), Some(r)) = (left.as_i64(), right.as_i64()) {
+        return Some(l.cmp(&r));
+    }
+    if let (Some(l), Some(r)) = (left.as_u64(), right.as_u64()) {
+        return Some(l.cmp(&r));
+    }
+    // A negative integer and one above `i64::MAX`
+    if left.is_i64() && right.is_u64() {
+        return Some(Ordering::Less);
+    }
+    if left.is_u64() && right.is_i64() {
+        return Some(Ordering::Greater);
+    }
+    left.as_f64()?.partial_cmp(&right.as_f64()?)
+}
+
+/// Loose coercion of booleans, `null` and numeric strings to numbers
+fn coerce_number(val: &Value) -> Option<Number> {
+    match val {
+        Value::Number(n) => Some(n.clone()),
+        Value::Bool(b) => Some(Number::from(*b as u8)),
+        Value::Null => Some(Number::from(0)),
+        Value::String(s) => {
+            let s = s.trim();
+            if let Ok(i) = s.parse::<i64>() {
+                Some(Number::from(i))
+            } else if let Ok(u) = s.parse::<u64>() {
+                Some(Number::from(u))
+            } else {
+                s.parse::<f64>().ok().and_then(Number::from_f64)
+            }
+        }
+        Value::Array(_) | Value::Object(_) => None,
+    }
+}
+
+fn same_type(left: &Value, right: &Value) -> bool {
+    std::mem::discriminant(left) == std::mem::discriminant(right)
+}
+
+/// Array elements, object keys or substrings
+fn contains(haystack: &Value, needle: &Value, mode: Comparison) -> bool {
+    match (haystack, needle) {
+        (Value::Array(items), _) => items.iter().any(|item| equals(item, needle, mode)),
+        (Value::Object(map), Value::String(key)) => map.contains_key(key),
+        (Value::String(s), Value::String(sub)) => s.contains(sub.as_str()),
+        _ => false,
+    }
+}
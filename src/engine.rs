@@ -1,23 +1,268 @@
 use crate::{
-    nodes::{CompareOp, Condition, ForLoop, If, Include, LocalValue, Node, Operand},
-    templates::Templates,
+    compare::{compare_values, is_truthy},
+    error::Error,
+    helpers::{ClassList, EscapeAttr, StepRange, StyleList, range},
+    nodes::{
+        ArithOp, Attribute, Call, ClassBinding, Condition, ForLoop, If, Include, Iterable,
+        LocalValue, LoopLocal, Node, Operand, StyleBinding, Tag,
+    },
+    output::{OutputMode, WriteMode, write_plain},
+    templates::{Global, Templates},
+    visit::Visit,
 };
-use serde_json::Value;
-use std::collections::HashMap;
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+use std::cell::{Cell, OnceCell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Write};
 
 pub struct ContextStack<'a> {
-    scopes: Vec<HashMap<String, serde_json::Value>>,
+    /// Loop items borrowed from the context are not cloned into a scope
+    scopes: Vec<HashMap<String, Cow<'a, Value>>>,
     global: &'a serde_json::Value,
+    env: Env<'a>,
+    resolved: Option<&'a Resolved>,
+    /// Collects `@defer` blocks waiting on resolvers when rendering out of order
+    deferred: Option<Vec<Deferred>>,
+    /// The output mode of the template being rendered
+    mode: OutputMode,
+}
+
+/// What every stack in one render shares: the globals and functions of `Templates`, and per render state
+#[derive(Clone, Copy)]
+pub struct Env<'a> {
+    templates: &'a Templates,
+    state: &'a RenderState,
+}
+
+impl<'a> Env<'a> {
+    pub fn new(templates: &'a Templates, state: &'a RenderState) -> Self {
+        Self { templates, state }
+    }
+
+    fn get(&self, key: &str) -> Option<&'a Value> {
+        match self.templates.global(key)? {
+            Global::Value(val) => Some(val),
+            Global::Function(f) => self
+                .state
+                .computed
+                .get(key)
+                .map(|cell| cell.get_or_init(|| f())),
+        }
+    }
+
+    /// Call a registered function, keeping the first error of the render.
+    ///
+    /// When an async render renders a node again after its resolvers finished, the calls it
+    /// made before are replayed instead of made again.
+    fn call(&self, name: &str, args: &[Value], named: &Map<String, Value>) -> Option<Value> {
+        let call = || match self.templates.function(name) {
+            Some(function) => function.call(args, named),
+            None => Err("unknown function".to_string()),
+        };
+        let result = match self.state.calls.borrow_mut().as_mut() {
+            Some(log) => log.call(name, args, named, call),
+            None => call(),
+        };
+        result
+            .map_err(|message| {
+                self.error(Error::Function {
+                    name: name.to_string(),
+                    message,
+                })
+            })
+            .ok()
+    }
+
+    /// Keep the first error of the render, for `try_render`
+    fn error(&self, error: Error) {
+        self.state.error.borrow_mut().get_or_insert(error);
+    }
+}
+
+/// Values of global functions, computed at most once per render, and the first error
+pub struct RenderState {
+    computed: HashMap<String, OnceCell<Value>>,
+    /// Function calls of the node being rendered, while it may have to be rendered again
+    calls: RefCell<Option<CallLog>>,
+    error: RefCell<Option<Error>>,
+}
+
+impl RenderState {
+    pub fn new(templates: &Templates) -> Self {
+        Self {
+            computed: templates
+                .global_functions()
+                .map(|key| (key.to_string(), OnceCell::new()))
+                .collect(),
+            calls: RefCell::new(None),
+            error: RefCell::new(None),
+        }
+    }
+
+    pub fn take_error(&self) -> Option<Error> {
+        self.error.borrow_mut().take()
+    }
+
+    /// Record the function calls of the next node, replaying the calls `log` holds from an
+    /// earlier render of it
+    #[cfg_attr(not(feature = "async"), allow(dead_code))]
+    pub fn replay_calls(&self, mut log: CallLog) {
+        log.next = 0;
+        *self.calls.borrow_mut() = Some(log);
+    }
+
+    /// Stop recording function calls, returning them
+    #[cfg_attr(not(feature = "async"), allow(dead_code))]
+    pub fn take_calls(&self) -> CallLog {
+        self.calls.borrow_mut().take().unwrap_or_default()
+    }
+}
+
+/// A function call with its name, arguments and result
+type LoggedCall = (
+    String,
+    Vec<Value>,
+    Map<String, Value>,
+    Result<Value, String>,
+);
+
+/// Function calls made while rendering a node that waits on a resolver, in order
+#[derive(Default)]
+pub struct CallLog {
+    calls: Vec<LoggedCall>,
+    next: usize,
+}
+
+impl CallLog {
+    /// Replay the next call if it has the same arguments as last time, or make it. Calls after
+    /// one that changed, like one that got a resolver value that was missing before, are made again.
+    fn call(
+        &mut self,
+        name: &str,
+        args: &[Value],
+        named: &Map<String, Value>,
+        call: impl FnOnce() -> Result<Value, String>,
+    ) -> Result<Value, String> {
+        if let Some((n, a, m, result)) = self.calls.get(self.next)
+            && n == name
+            && a == args
+            && m == named
+        {
+            self.next += 1;
+            return result.clone();
+        }
+        self.calls.truncate(self.next);
+        let result = call();
+        self.calls.push((
+            name.to_string(),
+            args.to_vec(),
+            named.clone(),
+            result.clone(),
+        ));
+        self.next += 1;
+        result
+    }
+}
+
+/// Values of async resolvers, filled in by the caller while a render waits on them
+#[cfg_attr(not(feature = "async"), allow(dead_code))]
+#[derive(Default)]
+pub struct Resolved {
+    pub keys: HashSet<String>,
+    pub values: HashMap<String, Result<Value, String>>,
+    pending: RefCell<Vec<String>>,
+    failed: Cell<bool>,
+    next_id: Cell<usize>,
+}
+
+#[cfg_attr(not(feature = "async"), allow(dead_code))]
+impl Resolved {
+    pub fn new(keys: HashSet<String>) -> Self {
+        Self {
+            keys,
+            ..Default::default()
+        }
+    }
+
+    /// Resolver keys that were looked up before they had a value, in order
+    pub fn take_pending(&self) -> Vec<String> {
+        std::mem::take(&mut self.pending.borrow_mut())
+    }
+
+    /// Whether a failed resolver was looked up
+    pub fn take_failed(&self) -> bool {
+        self.failed.replace(false)
+    }
+
+    fn next_id(&self) -> usize {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        id
+    }
+}
+
+/// A `@defer` block whose placeholder was written, to be rendered once `key` is resolved
+#[cfg_attr(not(feature = "async"), allow(dead_code))]
+pub struct Deferred {
+    pub id: usize,
+    pub key: String,
+    pub include: Include,
+    pub scopes: Vec<HashMap<String, Value>>,
+    pub content_html: Option<String>,
+    /// The output mode of the template the block is in
+    pub mode: OutputMode,
+    /// Function calls of the last time the block was rendered
+    pub calls: CallLog,
 }
 
 impl<'a> ContextStack<'a> {
-    pub fn new(global: &'a serde_json::Value) -> Self {
+    pub fn new(global: &'a serde_json::Value, env: Env<'a>) -> Self {
         Self {
             scopes: Vec::new(),
             global,
+            env,
+            resolved: None,
+            deferred: None,
+            mode: OutputMode::Html,
         }
     }
 
+    pub fn with_mode(mut self, mode: OutputMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Look up resolver values, optionally writing placeholders for `@defer` blocks that wait on them
+    #[cfg_attr(not(feature = "async"), allow(dead_code))]
+    pub fn with_resolved(
+        global: &'a serde_json::Value,
+        env: Env<'a>,
+        scopes: Vec<HashMap<String, Value>>,
+        resolved: &'a Resolved,
+        out_of_order: bool,
+    ) -> Self {
+        Self {
+            scopes: scopes
+                .into_iter()
+                .map(|scope| scope.into_iter().map(|(k, v)| (k, Cow::Owned(v))).collect())
+                .collect(),
+            global,
+            env,
+            resolved: Some(resolved),
+            deferred: out_of_order.then(Vec::new),
+            mode: OutputMode::Html,
+        }
+    }
+
+    #[cfg_attr(not(feature = "async"), allow(dead_code))]
+    pub fn take_deferred(&mut self) -> Vec<Deferred> {
+        self.deferred
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
     pub fn push_scope(&mut self) {
         self.scopes.push(HashMap::new());
     }
@@ -26,44 +271,78 @@ impl<'a> ContextStack<'a> {
         self.scopes.pop();
     }
 
-    pub fn set(&mut self, key: String, value: serde_json::Value) {
+    pub fn set(&mut self, key: String, value: Cow<'a, Value>) {
         if let Some(scope) = self.scopes.last_mut() {
             scope.insert(key, value);
         }
     }
 
+    /// Look up `key` in the scopes, the render context, async resolvers and then env
     pub fn get(&self, key: &str) -> Option<&serde_json::Value> {
         for scope in self.scopes.iter().rev() {
             if let Some(val) = scope.get(key) {
                 return Some(val);
             }
         }
-        self.global.get(key)
+        self.get_outer(key)
+    }
+
+    /// Everything but the scopes lives for the whole render
+    fn get_outer(&self, key: &str) -> Option<&'a serde_json::Value> {
+        if let Some(val) = self.global.get(key) {
+            return Some(val);
+        }
+
+        let Some(resolved) = self.resolved.filter(|r| r.keys.contains(key)) else {
+            return self.env.get(key);
+        };
+        match resolved.values.get(key) {
+            Some(Ok(val)) => Some(val),
+            Some(Err(_)) => {
+                resolved.failed.set(true);
+                None
+            }
+            None => {
+                let mut pending = resolved.pending.borrow_mut();
+                if !pending.iter().any(|pending| pending == key) {
+                    pending.push(key.to_string());
+                }
+                None
+            }
+        }
     }
 }
 
+/// Render nodes directly into `out`.
+///
+/// Only the body of a `@defer` is buffered, since it is written wherever the partial puts `{{ content }}`.
 pub fn render_nodes(
     nodes: &[Node],
     ctx_stack: &mut ContextStack,
     templates: &Templates,
     content_html: Option<&str>,
-) -> String {
-    let mut out = String::new();
-
+    out: &mut dyn Write,
+) -> fmt::Result {
     for n in nodes {
         match n {
-            Node::Text(s) => out.push_str(s),
+            Node::Text(s) => out.write_str(s)?,
 
             Node::VariableBlock(path) => {
                 if path.len() == 1 && path[0] == "__CONTENT__" {
                     if let Some(html) = content_html {
-                        out.push_str(html);
+                        out.write_str(html)?;
                     }
-                } else if let Some(val) = resolve_path(path, ctx_stack) {
-                    out.push_str(&value_to_string(val));
+                } else {
+                    let mode = ctx_stack.mode;
+                    mode.write_value(resolve_path(path, ctx_stack), out)?;
                 }
             }
 
+            Node::Call(call) => {
+                let val = call_function(call, ctx_stack);
+                ctx_stack.mode.write_value(val.as_ref(), out)?;
+            }
+
             Node::If(If {
                 conditions,
                 otherwise,
@@ -71,99 +350,385 @@ pub fn render_nodes(
                 let mut rendered = false;
                 for (cond, body) in conditions {
                     if evaluate_condition(cond, ctx_stack) {
-                        out.push_str(&render_nodes(body, ctx_stack, templates, content_html));
+                        render_nodes(body, ctx_stack, templates, content_html, out)?;
                         rendered = true;
                         break;
                     }
                 }
                 if !rendered && let Some(body) = otherwise {
-                    out.push_str(&render_nodes(body, ctx_stack, templates, content_html));
+                    render_nodes(body, ctx_stack, templates, content_html, out)?;
                 }
             }
 
-            Node::Forloop(ForLoop {
-                value,
-                container,
-                body,
-            }) => {
-                let items_opt = resolve_path(container, ctx_stack)
-                    .and_then(|v| v.as_array())
-                    .map(|a| a.to_vec());
+            Node::Forloop(for_loop) => {
+                let ForLoop {
+                    value,
+                    container,
+                    filter,
+                    limit,
+                    track,
+                    aliases,
+                    body,
+                } = for_loop.as_ref();
+                let items_opt = loop_items(container, ctx_stack);
+                let limit = match limit {
+                    Some(limit) => resolve_limit(limit, ctx_stack),
+                    None => Some(usize::MAX),
+                };
 
-                if let Some(items) = items_opt {
+                if let (Some(items), Some(mut limit)) = (items_opt, limit) {
+                    // Only strict mode looks for duplicate keys
+                    let mut seen = track
+                        .as_ref()
+                        .filter(|_| ctx_stack.env.templates.strict())
+                        .map(|track| (track, HashSet::new()));
                     ctx_stack.push_scope();
-                    for item in items.into_iter().enumerate() {
-                        ctx_stack.set(value.clone(), item.1);
-                        ctx_stack.set("index".into(), Value::from(item.0));
-                        out.push_str(&render_nodes(body, ctx_stack, templates, content_html));
+                    let filter = filter.as_ref();
+                    // `last` and `count` need the number of rendered items up front
+                    let needs_count = aliases
+                        .iter()
+                        .any(|(_, local)| matches!(local, LoopLocal::Last | LoopLocal::Count));
+                    if needs_count {
+                        limit = match (filter, items.size_hint()) {
+                            (None, (len, Some(max))) if len == max => limit.min(len),
+                            // Count the kept items in a pass of their own, instead of holding them
+                            _ => {
+                                let mut count = 0;
+                                for item in loop_items(container, ctx_stack).into_iter().flatten() {
+                                    if count >= limit {
+                                        break;
+                                    }
+                                    ctx_stack.set(value.clone(), item);
+                                    if filter
+                                        .is_none_or(|filter| evaluate_condition(filter, ctx_stack))
+                                    {
+                                        count += 1;
+                                    }
+                                }
+                                count
+                            }
+                        };
+                    }
+                    // Counts rendered items, so `index` skips filtered ones
+                    let mut index = 0;
+                    for item in items {
+                        if index >= limit {
+                            break;
+                        }
+                        ctx_stack.set(value.clone(), item);
+                        if let Some(filter) = filter
+                            && !evaluate_condition(filter, ctx_stack)
+                        {
+                            continue;
+                        }
+                        ctx_stack.set("index".into(), Cow::Owned(Value::from(index)));
+                        for (name, local) in aliases {
+                            let value = match local {
+                                LoopLocal::Index => Value::from(index),
+                                LoopLocal::First => Value::from(index == 0),
+                                LoopLocal::Last => Value::from(index + 1 == limit),
+                                LoopLocal::Even => Value::from(index % 2 == 0),
+                                LoopLocal::Odd => Value::from(index % 2 == 1),
+                                LoopLocal::Count => Value::from(limit),
+                            };
+                            ctx_stack.set(name.clone(), Cow::Owned(value));
+                        }
+                        if let Some((track, seen)) = &mut seen {
+                            check_track_key(track, seen, ctx_stack);
+                        }
+                        render_nodes(body, ctx_stack, templates, content_html, out)?;
+                        index += 1;
                     }
                     ctx_stack.pop_scope();
                 }
             }
 
-            Node::Include(Include {
-                path,
-                body,
-                local_ctx,
+            Node::Include(include) => {
+                let has_fallback = include.placeholder.is_some() || include.loading.is_some();
+                if has_fallback && ctx_stack.deferred.is_some() {
+                    defer_include(include, ctx_stack, templates, content_html, out)?;
+                } else {
+                    render_include(include, ctx_stack, templates, content_html, out)?;
+                }
+            }
+
+            Node::Tag(Tag {
+                name,
+                attributes,
+                self_closing,
             }) => {
-                if let Some(partial_nodes) = templates.get(path) {
-                    let parent_rendered_content = render_nodes(body, ctx_stack, templates, None);
-
-                    let mut partial_stack = ContextStack::new(Default::default());
-                    partial_stack.push_scope();
-
-                    for (k, local_val) in local_ctx {
-                        match local_val {
-                            LocalValue::Literal(val) => partial_stack.set(k.clone(), val.clone()),
-                            LocalValue::Path(path) => {
-                                if let Some(val) = resolve_path(path, ctx_stack) {
-                                    partial_stack.set(k.clone(), val.clone());
-                                } else {
-                                    partial_stack.set(k.clone(), serde_json::Value::Null);
+                write!(out, "<{}", name)?;
+                for attribute in attributes {
+                    match attribute {
+                        Attribute::Static { name, value: None } => write!(out, " {}", name)?,
+                        Attribute::Static {
+                            name,
+                            value: Some(value),
+                        } => {
+                            write!(out, " {}=\"", name)?;
+                            render_nodes(value, ctx_stack, templates, content_html, out)?;
+                            out.write_char('"')?;
+                        }
+                        Attribute::Bound {
+                            name,
+                            expr,
+                            boolean,
+                        } => {
+                            let value = binding_value(expr, ctx_stack);
+                            if *boolean {
+                                if value.as_deref().is_some_and(is_truthy) {
+                                    write!(out, " {}", name)?;
                                 }
+                            } else if let Some(value) = value.filter(|v| !v.is_null()) {
+                                write!(out, " {}=\"", name)?;
+                                write_plain(&value, &mut EscapeAttr(&mut *out))?;
+                                out.write_char('"')?;
                             }
                         }
+                        Attribute::Class(bindings) => {
+                            let mut classes = ClassList::default();
+                            for binding in bindings {
+                                match binding {
+                                    ClassBinding::Static(value) => {
+                                        let mut names = String::new();
+                                        render_nodes(
+                                            value, ctx_stack, templates, None, &mut names,
+                                        )?;
+                                        classes.add(&names);
+                                    }
+                                    ClassBinding::Toggle { name, cond } => {
+                                        classes.toggle(name, evaluate_condition(cond, ctx_stack))
+                                    }
+                                    ClassBinding::Expr(expr) => {
+                                        match binding_value(expr, ctx_stack).as_deref() {
+                                            Some(Value::String(names)) => classes.add(names),
+                                            Some(Value::Array(items)) => {
+                                                for names in items.iter().filter_map(Value::as_str)
+                                                {
+                                                    classes.add(names);
+                                                }
+                                            }
+                                            Some(Value::Object(map)) => {
+                                                for (names, on) in map {
+                                                    classes.toggle(names, is_truthy(on));
+                                                }
+                                            }
+                                            _ => {}
+                                        }
+                                    }
+                                }
+                            }
+                            classes.write_attribute(out)?;
+                        }
+                        Attribute::Style(bindings) => {
+                            let mut style = StyleList::default();
+                            for binding in bindings {
+                                match binding {
+                                    StyleBinding::Static(value) => {
+                                        let mut css = String::new();
+                                        render_nodes(value, ctx_stack, templates, None, &mut css)?;
+                                        style.add_declarations(&css);
+                                    }
+                                    StyleBinding::Property { name, value } => {
+                                        let value = binding_value(value, ctx_stack);
+                                        style.set(name, &value_text(value.as_deref())?);
+                                    }
+                                    StyleBinding::Expr(expr) => {
+                                        match binding_value(expr, ctx_stack).as_deref() {
+                                            Some(Value::String(css)) => style.add_declarations(css),
+                                            Some(Value::Object(map)) => {
+                                                for (name, value) in map {
+                                                    style.set(name, &value_text(Some(value))?);
+                                                }
+                                            }
+                                            _ => {}
+                                        }
+                                    }
+                                }
+                            }
+                            style.write_attribute(out)?;
+                        }
                     }
-
-                    let rendered = render_nodes(
-                        partial_nodes,
-                        &mut partial_stack,
-                        templates,
-                        Some(&parent_rendered_content),
-                    );
-                    out.push_str(&rendered);
-
-                    partial_stack.pop_scope();
-                } else {
-                    out.push_str(&format!("<!-- Missing defer: {} -->", path));
                 }
+                out.write_str(if *self_closing { "/>" } else { ">" })?;
             }
 
             Node::ContentPlaceholder => {
                 if let Some(html) = content_html {
-                    out.push_str(html);
+                    out.write_str(html)?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub fn render_include(
+    include: &Include,
+    ctx_stack: &mut ContextStack,
+    templates: &Templates,
+    content_html: Option<&str>,
+    out: &mut dyn Write,
+) -> fmt::Result {
+    let Include {
+        path,
+        body,
+        local_ctx,
+        ..
+    } = include;
+
+    if path.is_empty() {
+        return render_nodes(body, ctx_stack, templates, content_html, out);
+    }
+
+    if let Some(partial_nodes) = templates.get(path) {
+        let mut parent_rendered_content = String::new();
+        render_nodes(
+            body,
+            ctx_stack,
+            templates,
+            None,
+            &mut parent_rendered_content,
+        )?;
+
+        let mut partial_stack = ContextStack::new(Default::default(), ctx_stack.env)
+            .with_mode(templates.output_mode(path));
+        partial_stack.push_scope();
+
+        for (k, local_val) in local_ctx {
+            match local_val {
+                LocalValue::Literal(val) => partial_stack.set(k.clone(), Cow::Owned(val.clone())),
+                LocalValue::Path(path) => {
+                    let val = resolve_path_cow(path, ctx_stack);
+                    partial_stack.set(k.clone(), val.unwrap_or(Cow::Owned(Value::Null)));
                 }
             }
         }
+
+        render_nodes(
+            partial_nodes,
+            &mut partial_stack,
+            templates,
+            Some(&parent_rendered_content),
+            out,
+        )?;
+
+        partial_stack.pop_scope();
+    } else {
+        ctx_stack.mode.write_missing("defer", path, out)?;
+    }
+
+    Ok(())
+}
+
+/// Render a `@defer` with a `@placeholder` or `@loading` block when rendering out of order.
+///
+/// If the block needs a resolver that has no value yet, the fallback is written between
+/// `<!--sk-defer:id-->` markers and the block is queued to be swapped in later.
+fn defer_include(
+    include: &Include,
+    ctx_stack: &mut ContextStack,
+    templates: &Templates,
+    content_html: Option<&str>,
+    out: &mut dyn Write,
+) -> fmt::Result {
+    let Some(resolved) = ctx_stack.resolved else {
+        return render_include(include, ctx_stack, templates, content_html, out);
+    };
+
+    // Blocks inside render in place while probing, and the caller's flags are kept aside
+    let deferred = ctx_stack.deferred.take();
+    let outer_pending = resolved.take_pending();
+    let outer_failed = resolved.take_failed();
+
+    let mut probe = String::new();
+    render_include(include, ctx_stack, templates, content_html, &mut probe)?;
+    let pending = resolved.take_pending().into_iter().next();
+    let failed = resolved.take_failed();
+
+    let mut job = None;
+    match pending {
+        Some(key) => {
+            let id = resolved.next_id();
+            write!(out, "<!--sk-defer:{}-->", id)?;
+            if let Some(fallback) = include.loading.as_ref().or(include.placeholder.as_ref()) {
+                render_nodes(fallback, ctx_stack, templates, content_html, out)?;
+            }
+            write!(out, "<!--/sk-defer:{}-->", id)?;
+            job = Some(Deferred {
+                id,
+                key,
+                include: include.clone(),
+                scopes: ctx_stack
+                    .scopes
+                    .iter()
+                    .map(|scope| {
+                        scope
+                            .iter()
+                            .map(|(k, v)| (k.clone(), v.clone().into_owned()))
+                            .collect()
+                    })
+                    .collect(),
+                content_html: content_html.map(String::from),
+                mode: ctx_stack.mode,
+                calls: CallLog::default(),
+            });
+        }
+        None if failed => {
+            if let Some(error) = &include.error {
+                render_nodes(error, ctx_stack, templates, content_html, out)?;
+            }
+        }
+        None => out.write_str(&probe)?,
+    }
+
+    resolved.take_pending();
+    resolved.take_failed();
+    *resolved.pending.borrow_mut() = outer_pending;
+    resolved.failed.set(outer_failed);
+
+    ctx_stack.deferred = deferred;
+    if let (Some(deferred), Some(job)) = (ctx_stack.deferred.as_mut(), job) {
+        deferred.push(job);
     }
 
-    out
+    Ok(())
+}
+
+/// Top-level context keys a template can look up
+pub fn root_keys(nodes: &[Node]) -> HashSet<&str> {
+    let mut keys = RootKeys(HashSet::new());
+    keys.visit_nodes(nodes);
+    keys.0
+}
+
+/// Collects the first name of every path
+struct RootKeys<'a>(HashSet<&'a str>);
+
+impl<'a> Visit<'a> for RootKeys<'a> {
+    fn visit_path(&mut self, path: &'a [String]) {
+        if let Some(first) = path.first() {
+            self.0.insert(first);
+        }
+    }
 }
 
 fn evaluate_condition(cond: &Condition, ctx_stack: &ContextStack) -> bool {
     match cond {
-        Condition::Literal(b) => *b,
-        Condition::Path(path) => evaluate_path_truthiness(path, ctx_stack),
+        Condition::Literal(v) => is_truthy(v),
+        Condition::Path(path) => resolve_path(path, ctx_stack).is_some_and(is_truthy),
+        Condition::Operand(operand) => {
+            resolve_operand(operand, ctx_stack).is_some_and(|v| is_truthy(&v))
+        }
         Condition::And(conds) => conds.iter().all(|c| evaluate_condition(c, ctx_stack)),
         Condition::Or(conds) => conds.iter().any(|c| evaluate_condition(c, ctx_stack)),
         Condition::Not(inner) => !evaluate_condition(inner, ctx_stack),
         Condition::Compare { left, op, right } => {
-            let l = resolve_operand(left, ctx_stack);
-            let r = resolve_operand(right, ctx_stack);
-            match (l, r) {
-                (Some(lv), Some(rv)) => compare_values(&lv, op, &rv),
-                _ => false,
-            }
+            // Missing values compare as null
+            let l = resolve_operand(left, ctx_stack).unwrap_or_default();
+            let r = resolve_operand(right, ctx_stack).unwrap_or_default();
+            compare_values(&l, op, &r, ctx_stack.env.templates.comparison())
         }
     }
 }
@@ -172,95 +737,204 @@ fn resolve_operand(opnd: &Operand, ctx_stack: &ContextStack) -> Option<Value> {
     match opnd {
         Operand::Literal(v) => Some(v.clone()),
         Operand::Path(p) => resolve_path(p, ctx_stack).cloned(),
+        Operand::Call(call) => call_function(call, ctx_stack),
+        Operand::Arith { left, op, right } => {
+            let l = resolve_operand(left, ctx_stack)?;
+            let r = resolve_operand(right, ctx_stack)?;
+            arithmetic(&l, op, &r)
+        }
     }
 }
 
-fn compare_values(left: &Value, op: &CompareOp, right: &Value) -> bool {
-    match (left, right) {
-        (Value::String(ls), Value::String(rs)) => match op {
-            CompareOp::Eq => ls == rs,
-            CompareOp::Ne => ls != rs,
-            CompareOp::Lt => ls < rs,
-            CompareOp::Gt => ls > rs,
-            CompareOp::Le => ls <= rs,
-            CompareOp::Ge => ls >= rs,
-        },
-        (Value::Number(ln), Value::Number(rn)) => {
-            let lf = ln.as_f64().unwrap_or(0.0);
-            let rf = rn.as_f64().unwrap_or(0.0);
-            match op {
-                CompareOp::Eq => lf == rf,
-                CompareOp::Ne => lf != rf,
-                CompareOp::Lt => lf < rf,
-                CompareOp::Gt => lf > rf,
-                CompareOp::Le => lf <= rf,
-                CompareOp::Ge => lf >= rf,
-            }
+/// Integer arithmetic where possible, so `7 / 2` is `3.5` but `6 / 2` is `3`.
+/// Anything but numbers, overflow and division by zero give no value.
+fn arithmetic(left: &Value, op: &ArithOp, right: &Value) -> Option<Value> {
+    let (Value::Number(ln), Value::Number(rn)) = (left, right) else {
+        return None;
+    };
+
+    if let (Some(l), Some(r)) = (ln.as_i64(), rn.as_i64()) {
+        let exact = match op {
+            ArithOp::Add => l.checked_add(r),
+            ArithOp::Sub => l.checked_sub(r),
+            ArithOp::Mul => l.checked_mul(r),
+            ArithOp::Div if l.checked_rem(r) == Some(0) => l.checked_div(r),
+            ArithOp::Div => None,
+            ArithOp::Rem => l.checked_rem(r),
+        };
+        if let Some(result) = exact {
+            return Some(Value::from(result));
+        }
+        if matches!(op, ArithOp::Rem) || r == 0 {
+            return None;
         }
-        (Value::Bool(lb), Value::Bool(rb)) => match op {
-            CompareOp::Eq => lb == rb,
-            CompareOp::Ne => lb != rb,
-            _ => false,
-        },
-        _ => match op {
-            CompareOp::Eq => left == right,
-            CompareOp::Ne => left != right,
-            _ => false,
-        },
     }
+
+    let (l, r) = (ln.as_f64()?, rn.as_f64()?);
+    let result = match op {
+        ArithOp::Add => l + r,
+        ArithOp::Sub => l - r,
+        ArithOp::Mul => l * r,
+        ArithOp::Div => l / r,
+        ArithOp::Rem => l % r,
+    };
+    serde_json::Number::from_f64(result).map(Value::Number)
 }
 
-fn evaluate_path_truthiness(path: &[String], ctx_stack: &ContextStack) -> bool {
-    if path.len() == 1 {
-        let raw = &path[0];
-        match raw.as_str() {
-            "true" => return true,
-            "false" => return false,
-            _ => {
-                if let Ok(num) = raw.parse::<f64>() {
-                    return num != 0.0;
-                }
+/// The items of a `@for`, or `None` when there is nothing to go over
+fn loop_items<'a>(
+    container: &Iterable,
+    ctx_stack: &ContextStack<'a>,
+) -> Option<Box<dyn Iterator<Item = Cow<'a, Value>> + 'a>> {
+    match container {
+        Iterable::Path(path) => match resolve_path_cow(path, ctx_stack) {
+            Some(Cow::Borrowed(Value::Array(items))) => {
+                Some(Box::new(items.iter().map(Cow::Borrowed)))
             }
-        }
+            Some(Cow::Owned(Value::Array(items))) => {
+                Some(Box::new(items.into_iter().map(Cow::Owned)))
+            }
+            None | Some(Cow::Borrowed(Value::Null) | Cow::Owned(Value::Null)) => None,
+            Some(other) => {
+                ctx_stack.env.error(Error::Render(format!(
+                    "`@for` over `{}` expects an array, got {}",
+                    path.join("."),
+                    kind(&other)
+                )));
+                None
+            }
+        },
+        Iterable::Range { start, end, step } => resolve_range(start, end, step.as_ref(), ctx_stack)
+            .map(|range| Box::new(range.map(|i| Cow::Owned(i.into()))) as Box<_>),
     }
+}
+
+fn resolve_range(
+    start: &Operand,
+    end: &Operand,
+    step: Option<&Operand>,
+    ctx_stack: &ContextStack,
+) -> Option<StepRange> {
+    let integer = |operand: &Operand| resolve_operand(operand, ctx_stack)?.as_i64();
+    let bounds = (|| {
+        Some((
+            integer(start)?,
+            integer(end)?,
+            step.map_or(Some(1), integer)?,
+        ))
+    })();
 
-    if let Some(val) = resolve_path(path, ctx_stack) {
-        match val {
-            Value::Bool(b) => *b,
-            Value::Number(n) => n.as_f64().is_some_and(|f| f != 0.0),
-            Value::String(s) => !s.is_empty(),
-            Value::Null => false,
-            Value::Array(a) => !a.is_empty(),
-            Value::Object(o) => !o.is_empty(),
+    match bounds {
+        Some((start, end, step)) if step != 0 => Some(range(start, end, step)),
+        Some(_) => {
+            ctx_stack
+                .env
+                .error(Error::Render("`range` step can't be 0".to_string()));
+            None
+        }
+        None => {
+            ctx_stack
+                .env
+                .error(Error::Render("`range` expects integers".to_string()));
+            None
         }
-    } else {
-        false
     }
 }
 
-fn value_to_string(v: &Value) -> String {
-    match v {
-        Value::String(s) => s.clone(),
-        Value::Bool(b) => {
-            if *b {
-                "true".into()
-            } else {
-                "false".into()
-            }
-        }
-        Value::Number(n) => n.to_string(),
-        Value::Null => "".into(),
-        other => other.to_string(),
+/// Strict mode reports two items of a `@for` with the same `track` key
+fn check_track_key(track: &Operand, seen: &mut HashSet<String>, ctx_stack: &ContextStack) {
+    let Some(key) = resolve_operand(track, ctx_stack) else {
+        return;
+    };
+    let key = key.to_string();
+    if !seen.insert(key.clone()) {
+        ctx_stack.env.error(Error::Render(format!(
+            "`@for` has two items with the `track` key {}",
+            key
+        )));
     }
 }
 
-fn resolve_path<'a>(path: &'a [String], ctx_stack: &'a ContextStack) -> Option<&'a Value> {
-    if path.is_empty() {
-        return None;
+/// `limit` takes a non-negative integer
+fn resolve_limit(limit: &Operand, ctx_stack: &ContextStack) -> Option<usize> {
+    let limit = resolve_operand(limit, ctx_stack)
+        .as_ref()
+        .and_then(Value::as_u64)
+        .and_then(|limit| usize::try_from(limit).ok());
+    if limit.is_none() {
+        ctx_stack.env.error(Error::Render(
+            "`limit` expects a non-negative integer".into(),
+        ));
     }
-    let mut value = ctx_stack.get(&path[0])?;
+    limit
+}
+
+/// The value of a `[name]="expr"` binding, `true` or `false` for comparisons
+fn binding_value<'a>(expr: &Condition, ctx_stack: &ContextStack<'a>) -> Option<Cow<'a, Value>> {
+    match expr {
+        Condition::Literal(value) => Some(Cow::Owned(value.clone())),
+        Condition::Path(path) => resolve_path_cow(path, ctx_stack),
+        Condition::Operand(operand) => resolve_operand(operand, ctx_stack).map(Cow::Owned),
+        cond => Some(Cow::Owned(Value::Bool(evaluate_condition(cond, ctx_stack)))),
+    }
+}
+
+/// A value as written by an interpolation
+fn value_text(value: Option<&Value>) -> Result<String, fmt::Error> {
+    let mut text = String::new();
+    if let Some(value) = value {
+        write_plain(value, &mut text)?;
+    }
+    Ok(text)
+}
+
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+/// Missing arguments are passed as `null`
+fn call_function(call: &Call, ctx_stack: &ContextStack) -> Option<Value> {
+    let resolve = |arg| resolve_operand(arg, ctx_stack).unwrap_or(Value::Null);
+    let args: Vec<Value> = call.args.iter().map(resolve).collect();
+    let named: Map<String, Value> = call
+        .named
+        .iter()
+        .map(|(k, arg)| (k.clone(), resolve(arg)))
+        .collect();
+    ctx_stack.env.call(&call.name, &args, &named)
+}
+
+fn resolve_path<'a>(path: &'a [String], ctx_stack: &'a ContextStack) -> Option<&'a Value> {
+    let (first, rest) = path.split_first()?;
+    lookup(ctx_stack.get(first)?, rest)
+}
+
+/// Resolve `path` to a value that doesn't borrow the stack, so the stack can change while it is used.
+/// Only values owned by a scope are cloned.
+fn resolve_path_cow<'a>(path: &[String], ctx_stack: &ContextStack<'a>) -> Option<Cow<'a, Value>> {
+    let (first, rest) = path.split_first()?;
+    let root = match ctx_stack
+        .scopes
+        .iter()
+        .rev()
+        .find_map(|scope| scope.get(first))
+    {
+        Some(Cow::Borrowed(val)) => *val,
+        Some(Cow::Owned(val)) => return lookup(val, rest).cloned().map(Cow::Owned),
+        None => ctx_stack.get_outer(first)?,
+    };
+    lookup(root, rest).map(Cow::Borrowed)
+}
 
-    for key in &path[1..] {
+fn lookup<'a>(mut value: &'a Value, path: &[String]) -> Option<&'a Value> {
+    for key in path {
         match value {
             Value::Object(map) => {
                 value = map.get(key)?;
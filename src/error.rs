@@ -0,0 +1,22 @@
+/// Error returned by `Templates::try_render`
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error {
+    /// No template is loaded under the key
+    MissingTemplate(String),
+    /// A function call failed, or the function is unknown or got the wrong number of arguments
+    Function { name: String, message: String },
+    /// A template used a value in a way that can't be rendered, like `@for` over a string
+    Render(String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::MissingTemplate(key) => write!(f, "missing template `{}`", key),
+            Error::Function { name, message } => write!(f, "`{}()`: {}", name, message),
+            Error::Render(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,110 @@
+//! Functions registered on `Templates`, callable from expressions like `{{ asset('app.css') }}`.
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::ops::{Bound, RangeBounds};
+use std::panic::RefUnwindSafe;
+use std::sync::Arc;
+
+use serde_json::{Map, Value};
+
+use crate::nodes::{Call, Node};
+use crate::validate::Diagnostic;
+use crate::visit::{Visit, walk_call};
+
+type FunctionFn =
+    dyn Fn(&[Value], &Map<String, Value>) -> Result<Value, String> + Send + Sync + RefUnwindSafe;
+
+#[derive(Clone)]
+pub struct Function {
+    min_args: usize,
+    max_args: Option<usize>,
+    f: Arc<FunctionFn>,
+}
+
+impl Function {
+    pub fn new<R, F, E>(arity: R, f: F) -> Self
+    where
+        R: RangeBounds<usize>,
+        F: Fn(&[Value], &Map<String, Value>) -> Result<Value, E>
+            + Send
+            + Sync
+            + RefUnwindSafe
+            + 'static,
+        E: Display,
+    {
+        let min_args = match arity.start_bound() {
+            Bound::Included(n) => *n,
+            Bound::Excluded(n) => n + 1,
+            Bound::Unbounded => 0,
+        };
+        let max_args = match arity.end_bound() {
+            Bound::Included(n) => Some(*n),
+            Bound::Excluded(n) => Some(n.saturating_sub(1)),
+            Bound::Unbounded => None,
+        };
+        Self {
+            min_args,
+            max_args,
+            f: Arc::new(move |args, named| f(args, named).map_err(|e| e.to_string())),
+        }
+    }
+
+    /// Check the number of positional arguments. Named arguments are up to the function.
+    pub fn check_arity(&self, count: usize) -> Result<(), String> {
+        let plural = |n: usize| if n == 1 { "argument" } else { "arguments" };
+        let expected = match self.max_args {
+            Some(max) if max == self.min_args => format!("{} {}", max, plural(max)),
+            Some(max) => format!("{} to {} arguments", self.min_args, max),
+            None => format!("at least {} {}", self.min_args, plural(self.min_args)),
+        };
+
+        if count < self.min_args || self.max_args.is_some_and(|max| count > max) {
+            Err(format!("expects {}, got {}", expected, count))
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn call(&self, args: &[Value], named: &Map<String, Value>) -> Result<Value, String> {
+        self.check_arity(args.len())?;
+        (self.f)(args, named)
+    }
+}
+
+/// Report calls to functions that are not registered or get the wrong number of arguments
+pub fn check_calls(
+    template: &str,
+    nodes: &[Node],
+    functions: &HashMap<String, Function>,
+) -> Vec<Diagnostic> {
+    let mut calls = Calls(Vec::new());
+    calls.visit_nodes(nodes);
+
+    calls
+        .0
+        .into_iter()
+        .filter_map(|call| {
+            let message = match functions.get(&call.name) {
+                Some(function) => function.check_arity(call.args.len()).err()?,
+                None => "unknown function".to_string(),
+            };
+            Some(Diagnostic {
+                template: template.to_string(),
+                line: None,
+                column: None,
+                message: format!("`{}()`: {}", call.name, message),
+            })
+        })
+        .collect()
+}
+
+/// Collects every call, including calls in the arguments of others
+struct Calls<'a>(Vec<&'a Call>);
+
+impl<'a> Visit<'a> for Calls<'a> {
+    fn visit_call(&mut self, call: &'a Call) {
+        self.0.push(call);
+        walk_call(self, call);
+    }
+}
@@ -0,0 +1,201 @@
+//! Helpers shared by the runtime engine and the code generated by `#[derive(Template)]`.
+//!
+//! They are re-exported from `typed` for the generated code only, and are not part of the API.
+
+use std::fmt::{self, Write};
+
+use serde::Serialize;
+
+/// Count from `start` up to but not including `end`, like `range()` in `@for`.
+///
+/// A negative `step` counts down, a `step` of 0 gives nothing.
+pub fn range(start: i64, end: i64, step: i64) -> StepRange {
+    StepRange {
+        next: start,
+        end,
+        step,
+    }
+}
+
+/// Iterator returned by [`range`], computing each number as it goes
+pub struct StepRange {
+    next: i64,
+    end: i64,
+    step: i64,
+}
+
+impl Iterator for StepRange {
+    type Item = i64;
+
+    fn next(&mut self) -> Option<i64> {
+        let current = self.next;
+        let in_range = match self.step.signum() {
+            1 => current < self.end,
+            -1 => current > self.end,
+            _ => false,
+        };
+        if !in_range {
+            return None;
+        }
+        // Past the end of i64 the range is done
+        self.next = current.checked_add(self.step).unwrap_or(self.end);
+        Some(current)
+    }
+
+    /// The exact number of items left, so `$count` does not have to go through them
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (next, end, step) = (self.next as i128, self.end as i128, self.step as i128);
+        let left = match step.signum() {
+            1 if next < end => (end - next - 1) / step + 1,
+            -1 if next > end => (next - end - 1) / -step + 1,
+            _ => 0,
+        };
+        match usize::try_from(left) {
+            Ok(left) => (left, Some(left)),
+            Err(_) => (usize::MAX, None),
+        }
+    }
+
+    fn count(self) -> usize {
+        self.size_hint().0
+    }
+}
+
+/// Writer for attribute values that escapes `&`, `"` and `<`
+pub struct EscapeAttr<'a>(pub &'a mut dyn Write);
+
+impl Write for EscapeAttr<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut last = 0;
+        for (i, c) in s.match_indices(['&', '"', '<']) {
+            self.0.write_str(&s[last..i])?;
+            self.0.write_str(match c {
+                "&" => "&amp;",
+                "\"" => "&quot;",
+                _ => "&lt;",
+            })?;
+            last = i + 1;
+        }
+        self.0.write_str(&s[last..])
+    }
+}
+
+/// Classes of a tag with `[class.name]` or `[ngClass]` bindings, without duplicates
+#[derive(Default)]
+pub struct ClassList(Vec<String>);
+
+impl ClassList {
+    /// Add space separated class names
+    pub fn add(&mut self, names: &str) {
+        for name in names.split_whitespace() {
+            if !self.0.iter().any(|class| class == name) {
+                self.0.push(name.to_string());
+            }
+        }
+    }
+
+    pub fn toggle(&mut self, names: &str, on: bool) {
+        if on {
+            self.add(names);
+        } else {
+            let names: Vec<&str> = names.split_whitespace().collect();
+            self.0.retain(|class| !names.contains(&class.as_str()));
+        }
+    }
+
+    /// Write ` class="..."`, or nothing without classes
+    pub fn write_attribute(&self, out: &mut dyn Write) -> fmt::Result {
+        if self.0.is_empty() {
+            return Ok(());
+        }
+        out.write_str(" class=\"")?;
+        EscapeAttr(out).write_str(&self.0.join(" "))?;
+        out.write_char('"')
+    }
+}
+
+/// Properties of a tag with `[style.property]` or `[ngStyle]` bindings. Later values replace earlier ones.
+#[derive(Default)]
+pub struct StyleList(Vec<(String, String)>);
+
+impl StyleList {
+    /// Add declarations like `color: red; width: 10px`
+    pub fn add_declarations(&mut self, css: &str) {
+        for declaration in css.split(';') {
+            if let Some((property, value)) = declaration.split_once(':') {
+                self.set(property.trim(), value.trim());
+            }
+        }
+    }
+
+    /// Set a property, with an optional unit like `width.px`. An empty value, like from `null`, is ignored.
+    pub fn set(&mut self, property: &str, value: &str) {
+        if value.is_empty() {
+            return;
+        }
+        let (property, unit) = property.split_once('.').unwrap_or((property, ""));
+        self.0.retain(|(p, _)| p != property);
+        self.0
+            .push((property.to_string(), format!("{}{}", value, unit)));
+    }
+
+    /// Write ` style="..."`, or nothing without properties
+    pub fn write_attribute(&self, out: &mut dyn Write) -> fmt::Result {
+        if self.0.is_empty() {
+            return Ok(());
+        }
+        out.write_str(" style=\"")?;
+        for (i, (property, value)) in self.0.iter().enumerate() {
+            if i > 0 {
+                out.write_str("; ")?;
+            }
+            write!(EscapeAttr(out), "{}: {}", property, value)?;
+        }
+        out.write_char('"')
+    }
+}
+
+/// Writer that escapes values for XML
+pub struct EscapeXml<'a>(pub &'a mut dyn Write);
+
+impl Write for EscapeXml<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut last = 0;
+        for (i, c) in s.match_indices(['&', '<', '>', '"', '\'']) {
+            self.0.write_str(&s[last..i])?;
+            self.0.write_str(match c {
+                "&" => "&amp;",
+                "<" => "&lt;",
+                ">" => "&gt;",
+                "\"" => "&quot;",
+                _ => "&apos;",
+            })?;
+            last = i + 1;
+        }
+        self.0.write_str(&s[last..])
+    }
+}
+
+/// Writer that escapes Markdown characters with a backslash
+pub struct EscapeMarkdown<'a>(pub &'a mut dyn Write);
+
+impl Write for EscapeMarkdown<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            if matches!(
+                c,
+                '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '#' | '|'
+            ) {
+                self.0.write_char('\\')?;
+            }
+            self.0.write_char(c)?;
+        }
+        Ok(())
+    }
+}
+
+/// Write a value as a JSON literal, for templates in JSON mode
+pub fn write_json<T: Serialize + ?Sized>(value: &T, out: &mut dyn Write) -> fmt::Result {
+    let json = serde_json::to_string(value).map_err(|_| fmt::Error)?;
+    out.write_str(&json)
+}
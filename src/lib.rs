@@ -1,6 +1,26 @@
+mod compare;
 mod engine;
-mod nodes;
-mod parser;
+mod error;
+mod functions;
+mod helpers;
+mod output;
+mod serialize;
+#[cfg(feature = "async")]
+mod stream;
 mod templates;
+pub mod typed;
 
+use skabelon_parser::{nodes, parser, validate, visit};
+
+pub use compare::Comparison;
+pub use error::Error;
+pub use output::OutputMode;
+pub use parser::ParseError;
+#[cfg(feature = "async")]
+pub use stream::{RenderAsync, RenderStream};
 pub use templates::Templates;
+pub use typed::Template;
+pub use validate::Diagnostic;
+
+#[cfg(feature = "macros")]
+pub use skabelon_macros::{Template, embed, validate};
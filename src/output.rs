@@ -0,0 +1,55 @@
+//! How the output of a template is written.
+//!
+//! The mode of a template is picked by the extension of its key, or set with
+//! `Templates::set_output_mode`. It decides how interpolated values are escaped and what is
+//! written for values and templates that are missing. Partials are written in their own mode.
+
+use std::fmt::{self, Write};
+
+use serde_json::Value;
+
+use crate::helpers::{EscapeMarkdown, EscapeXml};
+
+pub use skabelon_parser::output::OutputMode;
+
+/// Writing values in the mode of a template
+pub(crate) trait WriteMode {
+    fn write_value(self, value: Option<&Value>, out: &mut dyn Write) -> fmt::Result;
+    fn write_missing(self, what: &str, key: &str, out: &mut dyn Write) -> fmt::Result;
+}
+
+impl WriteMode for OutputMode {
+    /// Write an interpolated value, or what stands in for a missing one
+    fn write_value(self, value: Option<&Value>, out: &mut dyn Write) -> fmt::Result {
+        let Some(value) = value else {
+            return match self {
+                OutputMode::Json => out.write_str("null"),
+                _ => Ok(()),
+            };
+        };
+        match self {
+            OutputMode::Html | OutputMode::Text => write_plain(value, out),
+            OutputMode::Xml => write_plain(value, &mut EscapeXml(out)),
+            OutputMode::Markdown => write_plain(value, &mut EscapeMarkdown(out)),
+            OutputMode::Json => write!(out, "{}", value),
+        }
+    }
+
+    /// Write the note for a `@defer` or rendered template that is not loaded
+    fn write_missing(self, what: &str, key: &str, out: &mut dyn Write) -> fmt::Result {
+        match self {
+            OutputMode::Html | OutputMode::Xml => write!(out, "<!-- Missing {}: {} -->", what, key),
+            OutputMode::Text | OutputMode::Markdown | OutputMode::Json => Ok(()),
+        }
+    }
+}
+
+pub(crate) fn write_plain(v: &Value, out: &mut dyn Write) -> fmt::Result {
+    match v {
+        Value::String(s) => out.write_str(s),
+        Value::Bool(b) => out.write_str(if *b { "true" } else { "false" }),
+        Value::Number(n) => write!(out, "{}", n),
+        Value::Null => Ok(()),
+        other => write!(out, "{}", other),
+    }
+}
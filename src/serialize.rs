@@ -0,0 +1,219 @@
+//! Build a render context from any `Serialize` value.
+//!
+//! Only the top-level fields a template refers to are converted to `serde_json::Value`,
+//! the rest of the struct is skipped without being serialized. A field that is used is
+//! converted whole, so a template that only reads `user.name` still converts all of `user`.
+
+use std::collections::HashSet;
+
+use serde::Serialize;
+use serde::ser::{self, Impossible, SerializeMap, SerializeStruct};
+use serde_json::{Map, Value};
+
+/// Pick `keys` out of a struct or map. Other values give an empty context.
+pub fn pick_fields<T: Serialize + ?Sized>(value: &T, keys: &HashSet<&str>) -> Value {
+    let map = value.serialize(FieldPicker { keys }).unwrap_or_default();
+    Value::Object(map)
+}
+
+#[derive(Debug)]
+struct NotAMap;
+
+impl std::fmt::Display for NotAMap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("context must serialize to a map or struct")
+    }
+}
+
+impl std::error::Error for NotAMap {}
+
+impl ser::Error for NotAMap {
+    fn custom<T: std::fmt::Display>(_msg: T) -> Self {
+        NotAMap
+    }
+}
+
+struct FieldPicker<'a> {
+    keys: &'a HashSet<&'a str>,
+}
+
+struct PickedFields<'a> {
+    keys: &'a HashSet<&'a str>,
+    map: Map<String, Value>,
+    next_key: Option<String>,
+}
+
+impl PickedFields<'_> {
+    fn insert<T: Serialize + ?Sized>(&mut self, key: &str, value: &T) {
+        if self.keys.contains(key)
+            && let Ok(value) = serde_json::to_value(value)
+        {
+            self.map.insert(key.to_string(), value);
+        }
+    }
+}
+
+macro_rules! not_a_map {
+    ($($method:ident($($arg:ty),*)),* $(,)?) => {
+        $(
+            fn $method(self, $(_: $arg),*) -> Result<Self::Ok, Self::Error> {
+                Err(NotAMap)
+            }
+        )*
+    };
+}
+
+impl<'a> ser::Serializer for FieldPicker<'a> {
+    type Ok = Map<String, Value>;
+    type Error = NotAMap;
+    type SerializeSeq = Impossible<Self::Ok, NotAMap>;
+    type SerializeTuple = Impossible<Self::Ok, NotAMap>;
+    type SerializeTupleStruct = Impossible<Self::Ok, NotAMap>;
+    type SerializeTupleVariant = Impossible<Self::Ok, NotAMap>;
+    type SerializeMap = PickedFields<'a>;
+    type SerializeStruct = PickedFields<'a>;
+    type SerializeStructVariant = Impossible<Self::Ok, NotAMap>;
+
+    not_a_map!(
+        serialize_bool(bool),
+        serialize_i8(i8),
+        serialize_i16(i16),
+        serialize_i32(i32),
+        serialize_i64(i64),
+        serialize_u8(u8),
+        serialize_u16(u16),
+        serialize_u32(u32),
+        serialize_u64(u64),
+        serialize_f32(f32),
+        serialize_f64(f64),
+        serialize_char(char),
+        serialize_str(&str),
+        serialize_bytes(&[u8]),
+        serialize_unit_struct(&'static str),
+        serialize_unit_variant(&'static str, u32, &'static str),
+    );
+
+    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        Ok(Map::new())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Self::Ok, Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+        Ok(Map::new())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Self::Ok, Self::Error> {
+        Err(NotAMap)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        Err(NotAMap)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        Err(NotAMap)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        Err(NotAMap)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Err(NotAMap)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Ok(PickedFields {
+            keys: self.keys,
+            map: Map::new(),
+            next_key: None,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        self.serialize_map(None)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        Err(NotAMap)
+    }
+}
+
+impl SerializeMap for PickedFields<'_> {
+    type Ok = Map<String, Value>;
+    type Error = NotAMap;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Self::Error> {
+        self.next_key = match serde_json::to_value(key) {
+            Ok(Value::String(s)) => Some(s),
+            _ => None,
+        };
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        if let Some(key) = self.next_key.take() {
+            self.insert(&key, value);
+        }
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(self.map)
+    }
+}
+
+impl SerializeStruct for PickedFields<'_> {
+    type Ok = Map<String, Value>;
+    type Error = NotAMap;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Self::Error> {
+        self.insert(key, value);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(self.map)
+    }
+}
@@ -0,0 +1,511 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::ops::Deref;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use bytes::Bytes;
+use futures::future::{BoxFuture, FutureExt, Shared, join_all, select_all};
+use futures::stream::{self, BoxStream};
+use futures::{Stream, StreamExt};
+use serde_json::Value;
+
+use crate::engine::{
+    CallLog, ContextStack, Deferred, Env, RenderState, Resolved, render_include, render_nodes,
+};
+use crate::nodes::Node;
+use crate::output::WriteMode;
+use crate::templates::Templates;
+
+type ResolverFuture = BoxFuture<'static, Result<Value, String>>;
+type Resolver = Arc<dyn Fn() -> ResolverFuture + Send + Sync>;
+
+/// Replaces the fallback between `<!--sk-defer:id-->` markers with the content of `<template id="sk-defer-id">`
+const SWAP_SCRIPT: &str = r#"<script>function $sk(i){var w=document.createNodeIterator(document,128),s,n,x;while(n=w.nextNode())if(n.data==="sk-defer:"+i){s=n;break}var t=document.getElementById("sk-defer-"+i);if(!s||!t)return;n=s.nextSibling;while(n&&!(n.nodeType===8&&n.data==="/sk-defer:"+i)){x=n.nextSibling;n.remove();n=x}s.parentNode.insertBefore(t.content,n);s.remove();if(n)n.remove();t.remove()}</script>"#;
+
+impl Templates {
+    /// Render as a stream of chunks, for sending the start of a page before the rest is rendered.
+    ///
+    /// A chunk is yielded at each `@defer` on the top level of the template, so the markup
+    /// before a partial is on the wire while the partial renders.
+    pub fn render_stream<'a>(&'a self, path: &'a str, ctx: &'a Value) -> RenderStream<'a> {
+        RenderStream::new(
+            TemplatesRef::Borrowed(self),
+            path.into(),
+            Cow::Borrowed(ctx),
+        )
+    }
+
+    /// Like `render_stream`, but owns its arguments so it can be returned as a response body
+    pub fn render_stream_owned(
+        self: Arc<Self>,
+        path: impl Into<String>,
+        ctx: Value,
+    ) -> RenderStream<'static> {
+        RenderStream::new(
+            TemplatesRef::Shared(self),
+            Cow::Owned(path.into()),
+            Cow::Owned(ctx),
+        )
+    }
+
+    /// Render with context values computed by async resolvers, see `RenderAsync::with_resolver`.
+    ///
+    /// Example:
+    ///     let html = templates
+    ///         .render_async("page.html", &ctx)
+    ///         .with_resolver("recommendations", || async { fetch_recommendations().await })
+    ///         .await;
+    pub fn render_async<'a>(&'a self, path: &'a str, ctx: &'a Value) -> RenderAsync<'a> {
+        RenderAsync {
+            config: StreamConfig {
+                templates: TemplatesRef::Borrowed(self),
+                path: path.into(),
+                ctx: Cow::Borrowed(ctx),
+                resolvers: HashMap::new(),
+            },
+        }
+    }
+}
+
+fn boxed_resolver<F, Fut, E>(resolver: F) -> Resolver
+where
+    F: Fn() -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<Value, E>> + Send + 'static,
+    E: Display,
+{
+    Arc::new(move || resolver().map(|r| r.map_err(|e| e.to_string())).boxed())
+}
+
+/// Stream returned by `Templates::render_stream`.
+///
+/// `@defer` blocks with a `@placeholder` or `@loading` block that use a key from a
+/// resolver which has not finished are rendered out of order: the fallback is streamed in
+/// place, and the real content follows at the end of the page with a small script that
+/// swaps it in. If the resolver fails, the `@error` block is swapped in instead.
+pub struct RenderStream<'a> {
+    config: Option<StreamConfig<'a>>,
+    inner: Option<BoxStream<'a, Bytes>>,
+}
+
+impl<'a> RenderStream<'a> {
+    fn new(templates: TemplatesRef<'a>, path: Cow<'a, str>, ctx: Cow<'a, Value>) -> Self {
+        Self {
+            config: Some(StreamConfig {
+                templates,
+                path,
+                ctx,
+                resolvers: HashMap::new(),
+            }),
+            inner: None,
+        }
+    }
+
+    /// Compute the top-level `key` asynchronously, when the template first uses it.
+    ///
+    /// Keys in the render context take precedence over resolvers.
+    pub fn with_resolver<F, Fut, E>(mut self, key: &str, resolver: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Value, E>> + Send + 'static,
+        E: Display,
+    {
+        if let Some(config) = self.config.as_mut() {
+            config
+                .resolvers
+                .insert(key.to_string(), boxed_resolver(resolver));
+        }
+        self
+    }
+}
+
+impl Stream for RenderStream<'_> {
+    type Item = Bytes;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Bytes>> {
+        if self.inner.is_none()
+            && let Some(config) = self.config.take()
+        {
+            self.inner = Some(config.into_stream());
+        }
+
+        match self.inner.as_mut() {
+            Some(inner) => inner.poll_next_unpin(cx),
+            None => Poll::Ready(None),
+        }
+    }
+}
+
+/// Render returned by `Templates::render_async`, rendered when awaited.
+///
+/// Resolvers are only called when the template looks up their key, and each is called at
+/// most once per render. A failed resolver renders like a missing value.
+pub struct RenderAsync<'a> {
+    config: StreamConfig<'a>,
+}
+
+impl RenderAsync<'_> {
+    /// Compute the top-level `key` asynchronously, when the template first uses it.
+    ///
+    /// Keys in the render context take precedence over resolvers.
+    pub fn with_resolver<F, Fut, E>(mut self, key: &str, resolver: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Value, E>> + Send + 'static,
+        E: Display,
+    {
+        self.config
+            .resolvers
+            .insert(key.to_string(), boxed_resolver(resolver));
+        self
+    }
+}
+
+impl<'a> IntoFuture for RenderAsync<'a> {
+    type Output = String;
+    type IntoFuture = BoxFuture<'a, String>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        let config = self.config;
+        async move {
+            let Some(nodes) = config.templates.get(&config.path) else {
+                let mut out = String::new();
+                let mode = config.templates.output_mode(&config.path);
+                // Writing to a String never fails
+                let _ = mode.write_missing("template", &config.path, &mut out);
+                return out;
+            };
+            let mut resolved = Resolved::new(config.resolvers.keys().cloned().collect());
+            let state = RenderState::new(&config.templates);
+            // Top-level nodes that rendered without waiting on a resolver are kept, and the
+            // function calls of the others are replayed when they are rendered again
+            let mut outputs: Vec<Option<String>> = vec![None; nodes.len()];
+            let mut calls: Vec<CallLog> = nodes.iter().map(|_| CallLog::default()).collect();
+
+            loop {
+                let mut pending = Vec::new();
+                {
+                    let env = Env::new(&config.templates, &state);
+                    // Scopes only live inside a node, so one stack for all nodes is equivalent
+                    let mut ctx_stack =
+                        ContextStack::with_resolved(&config.ctx, env, Vec::new(), &resolved, false)
+                            .with_mode(config.templates.output_mode(&config.path));
+                    for ((node, output), calls) in nodes.iter().zip(&mut outputs).zip(&mut calls) {
+                        if output.is_some() {
+                            continue;
+                        }
+                        state.replay_calls(std::mem::take(calls));
+                        let mut out = String::new();
+                        // Writing to a String never fails
+                        let _ = render_nodes(
+                            std::slice::from_ref(node),
+                            &mut ctx_stack,
+                            &config.templates,
+                            None,
+                            &mut out,
+                        );
+                        resolved.take_failed();
+                        let keys = resolved.take_pending();
+                        let log = state.take_calls();
+                        if keys.is_empty() {
+                            *output = Some(out);
+                        } else {
+                            *calls = log;
+                        }
+                        for key in keys {
+                            if !pending.contains(&key) {
+                                pending.push(key);
+                            }
+                        }
+                    }
+                }
+
+                if pending.is_empty() {
+                    return outputs.into_iter().flatten().collect();
+                }
+                // Everything one pass found missing is awaited together
+                let results = join_all(pending.iter().map(|key| match config.resolvers.get(key) {
+                    Some(resolver) => resolver(),
+                    None => {
+                        futures::future::ready(Err(format!("no resolver for `{}`", key))).boxed()
+                    }
+                }))
+                .await;
+                resolved.values.extend(pending.into_iter().zip(results));
+            }
+        }
+        .boxed()
+    }
+}
+
+enum TemplatesRef<'a> {
+    Borrowed(&'a Templates),
+    Shared(Arc<Templates>),
+}
+
+impl Deref for TemplatesRef<'_> {
+    type Target = Templates;
+
+    fn deref(&self) -> &Templates {
+        match self {
+            TemplatesRef::Borrowed(t) => t,
+            TemplatesRef::Shared(t) => t,
+        }
+    }
+}
+
+struct StreamConfig<'a> {
+    templates: TemplatesRef<'a>,
+    path: Cow<'a, str>,
+    ctx: Cow<'a, Value>,
+    resolvers: HashMap<String, Resolver>,
+}
+
+impl<'a> StreamConfig<'a> {
+    fn into_stream(self) -> BoxStream<'a, Bytes> {
+        let resolved = Resolved::new(self.resolvers.keys().cloned().collect());
+        let render_state = RenderState::new(&self.templates);
+
+        let state = StreamState {
+            config: self,
+            resolved,
+            render_state,
+            running: HashMap::new(),
+            position: 0,
+            chunk: String::new(),
+            in_order_done: false,
+            deferred: Vec::new(),
+            swap_script_sent: false,
+            calls: CallLog::default(),
+        };
+
+        stream::unfold(state, |mut state| async move {
+            let chunk = state.next_chunk().await?;
+            Some((Bytes::from(chunk), state))
+        })
+        .boxed()
+    }
+}
+
+enum Step {
+    Chunk(String),
+    Wait(String),
+    Done,
+}
+
+struct StreamState<'a> {
+    config: StreamConfig<'a>,
+    resolved: Resolved,
+    render_state: RenderState,
+    running: HashMap<String, Shared<ResolverFuture>>,
+    position: usize,
+    chunk: String,
+    in_order_done: bool,
+    deferred: Vec<Deferred>,
+    swap_script_sent: bool,
+    /// Function calls of the node at `position`, replayed when it is rendered again
+    calls: CallLog,
+}
+
+impl StreamState<'_> {
+    async fn next_chunk(&mut self) -> Option<String> {
+        while !self.in_order_done {
+            match self.render_in_order() {
+                Step::Chunk(chunk) => return Some(chunk),
+                Step::Wait(key) => self.resolve(&key).await,
+                Step::Done => self.in_order_done = true,
+            }
+        }
+
+        loop {
+            if self.deferred.is_empty() {
+                return None;
+            }
+            if let Some(chunk) = self.render_deferred() {
+                return Some(chunk);
+            }
+            self.resolve_any().await;
+        }
+    }
+
+    /// Render the top-level nodes in order, until a chunk is ready or a resolver must be awaited
+    fn render_in_order(&mut self) -> Step {
+        let templates = &*self.config.templates;
+        let path = self.config.path.as_ref();
+        let Some(nodes) = templates.get(path) else {
+            self.in_order_done = true;
+            let mut out = String::new();
+            // Writing to a String never fails
+            let _ = templates
+                .output_mode(path)
+                .write_missing("template", path, &mut out);
+            return Step::Chunk(out);
+        };
+
+        // Scopes only live inside a node, so a fresh stack per chunk is equivalent
+        let env = Env::new(templates, &self.render_state);
+        let mut ctx_stack =
+            ContextStack::with_resolved(&self.config.ctx, env, Vec::new(), &self.resolved, true)
+                .with_mode(templates.output_mode(path));
+
+        while let Some(node) = nodes.get(self.position) {
+            let is_defer = matches!(node, Node::Include(_));
+            if is_defer && !self.chunk.is_empty() {
+                return Step::Chunk(std::mem::take(&mut self.chunk));
+            }
+
+            let len = self.chunk.len();
+            self.render_state
+                .replay_calls(std::mem::take(&mut self.calls));
+            // Writing to a String never fails
+            let _ = render_nodes(
+                std::slice::from_ref(node),
+                &mut ctx_stack,
+                templates,
+                None,
+                &mut self.chunk,
+            );
+            let calls = self.render_state.take_calls();
+
+            if let Some(key) = self.resolved.take_pending().into_iter().next() {
+                self.calls = calls;
+                // Render the node again once the value is there, sending what is ready first
+                self.resolved.take_failed();
+                self.chunk.truncate(len);
+                ctx_stack.take_deferred();
+                if self.chunk.is_empty() {
+                    return Step::Wait(key);
+                }
+                return Step::Chunk(std::mem::take(&mut self.chunk));
+            }
+            self.resolved.take_failed();
+
+            self.deferred.extend(ctx_stack.take_deferred());
+            self.position += 1;
+
+            if is_defer && !self.chunk.is_empty() {
+                return Step::Chunk(std::mem::take(&mut self.chunk));
+            }
+        }
+
+        if self.chunk.is_empty() {
+            Step::Done
+        } else {
+            Step::Chunk(std::mem::take(&mut self.chunk))
+        }
+    }
+
+    /// Render the deferred blocks whose data is ready, as one chunk of swaps
+    fn render_deferred(&mut self) -> Option<String> {
+        let templates = &*self.config.templates;
+        let mut chunk = String::new();
+
+        let mut waiting = Vec::new();
+        for mut deferred in std::mem::take(&mut self.deferred) {
+            let mut ctx_stack = ContextStack::with_resolved(
+                &self.config.ctx,
+                Env::new(templates, &self.render_state),
+                deferred.scopes.clone(),
+                &self.resolved,
+                false,
+            )
+            .with_mode(deferred.mode);
+
+            let mut html = String::new();
+            self.render_state
+                .replay_calls(std::mem::take(&mut deferred.calls));
+            let _ = render_include(
+                &deferred.include,
+                &mut ctx_stack,
+                templates,
+                deferred.content_html.as_deref(),
+                &mut html,
+            );
+            let calls = self.render_state.take_calls();
+
+            if let Some(key) = self.resolved.take_pending().into_iter().next() {
+                self.resolved.take_failed();
+                deferred.key = key;
+                deferred.calls = calls;
+                waiting.push(deferred);
+                continue;
+            }
+
+            if self.resolved.take_failed() {
+                html.clear();
+                if let Some(error) = &deferred.include.error {
+                    let _ = render_nodes(
+                        error,
+                        &mut ctx_stack,
+                        templates,
+                        deferred.content_html.as_deref(),
+                        &mut html,
+                    );
+                }
+                self.resolved.take_pending();
+                self.resolved.take_failed();
+            }
+
+            if !self.swap_script_sent {
+                chunk.push_str(SWAP_SCRIPT);
+                self.swap_script_sent = true;
+            }
+            chunk.push_str(&format!(
+                "<template id=\"sk-defer-{id}\">{html}</template><script>$sk({id})</script>",
+                id = deferred.id
+            ));
+        }
+        self.deferred = waiting;
+
+        if chunk.is_empty() { None } else { Some(chunk) }
+    }
+
+    fn start(&mut self, key: &str) -> Option<Shared<ResolverFuture>> {
+        if !self.running.contains_key(key) {
+            let resolver = self.config.resolvers.get(key)?;
+            self.running.insert(key.to_string(), resolver().shared());
+        }
+        self.running.get(key).cloned()
+    }
+
+    async fn resolve(&mut self, key: &str) {
+        let result = match self.start(key) {
+            Some(future) => future.await,
+            None => Err(format!("no resolver for `{}`", key)),
+        };
+        self.running.remove(key);
+        self.resolved.values.insert(key.to_string(), result);
+    }
+
+    /// Wait for the first resolver a deferred block is waiting on
+    async fn resolve_any(&mut self) {
+        let mut keys: Vec<String> = self.deferred.iter().map(|d| d.key.clone()).collect();
+        keys.sort();
+        keys.dedup();
+
+        let futures: Vec<_> = keys
+            .into_iter()
+            .filter_map(|key| {
+                let future = self.start(&key)?;
+                Some(future.map(move |result| (key, result)).boxed())
+            })
+            .collect();
+
+        if futures.is_empty() {
+            // Nothing can make progress, fail the blocks instead of waiting forever
+            for deferred in &self.deferred {
+                self.resolved
+                    .values
+                    .entry(deferred.key.clone())
+                    .or_insert_with(|| Err(format!("no resolver for `{}`", deferred.key)));
+            }
+            return;
+        }
+
+        let ((key, result), _, _) = select_all(futures).await;
+        self.running.remove(&key);
+        self.resolved.values.insert(key, result);
+    }
+}
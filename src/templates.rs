@@ -1,16 +1,41 @@
 use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::io;
+use std::ops::RangeBounds;
+use std::panic::RefUnwindSafe;
 use std::path::PathBuf;
+use std::sync::Arc;
 
-use serde_json::Value;
+use serde::Serialize;
+use serde_json::{Map, Value};
 
-use crate::engine::{ContextStack, render_nodes};
+use crate::compare::Comparison;
+use crate::engine::{ContextStack, Env, RenderState, render_nodes, root_keys};
+use crate::error::Error;
+use crate::functions::{Function, check_calls};
 use crate::nodes::Node;
-use crate::parser::parse_template;
+use crate::output::{OutputMode, WriteMode};
+use crate::parser::{ParseError, ParseOptions, parse_template_with_errors};
+use crate::serialize::pick_fields;
+use crate::validate::{Diagnostic, diagnose};
 
 #[derive(Default, Clone)]
 pub struct Templates {
     templates: HashMap<String, Vec<Node>>,
+    errors: HashMap<String, Vec<ParseError>>,
     glob: Option<String>,
+    globals: HashMap<String, Global>,
+    functions: HashMap<String, Function>,
+    comparison: Comparison,
+    strict: bool,
+    parse_options: ParseOptions,
+    output_modes: HashMap<String, OutputMode>,
+}
+
+#[derive(Clone)]
+pub(crate) enum Global {
+    Value(Value),
+    Function(Arc<dyn Fn() -> Value + Send + Sync + RefUnwindSafe>),
 }
 
 impl Templates {
@@ -28,9 +53,7 @@ impl Templates {
     fn load_as(&mut self, absolute_path: &str, relative_key: &str) {
         let content = std::fs::read_to_string(absolute_path)
             .unwrap_or_else(|e| panic!("Failed to read {}: {}", absolute_path, e));
-        let nodes = parse_template(&content);
-        let key = normalize_key(relative_key);
-        self.templates.insert(key.clone(), nodes);
+        self.insert(relative_key, &content);
     }
 
     /// Load using a glob, stripping the base directory from all matches.
@@ -60,9 +83,183 @@ impl Templates {
     }
 
     pub fn load_str(&mut self, key: &str, content: &str) {
-        let nodes = parse_template(content);
-        let rel_key = normalize_key(key);
-        self.templates.insert(rel_key.clone(), nodes);
+        self.insert(key, content);
+    }
+
+    fn insert(&mut self, key: &str, content: &str) {
+        let (nodes, errors) = parse_template_with_errors(content, &self.parse_options);
+        let key = normalize_key(key);
+        if errors.is_empty() {
+            self.errors.remove(&key);
+        } else {
+            self.errors.insert(key.clone(), errors);
+        }
+        self.templates.insert(key, nodes);
+    }
+
+    /// Check all loaded templates for syntax errors, `@defer` targets that are not loaded and
+    /// calls to functions that are not registered or get the wrong number of arguments.
+    ///
+    /// Templates are parsed leniently, so this is the place to catch mistakes before a page is hit.
+    pub fn validate(&self) -> Result<(), Vec<Diagnostic>> {
+        let mut keys: Vec<&String> = self.templates.keys().collect();
+        keys.sort();
+
+        let exists = |path: &str| self.get(path).is_some();
+        let diagnostics: Vec<Diagnostic> = keys
+            .into_iter()
+            .flat_map(|key| {
+                let errors = self.errors.get(key).map(Vec::as_slice).unwrap_or_default();
+                let nodes = &self.templates[key];
+                let mut diagnostics = diagnose(key, nodes, errors, &exists);
+                diagnostics.extend(check_calls(key, nodes, &self.functions));
+                diagnostics
+            })
+            .collect();
+
+        if diagnostics.is_empty() {
+            Ok(())
+        } else {
+            Err(diagnostics)
+        }
+    }
+
+    /// Make `name` available to every template, including partials.
+    ///
+    /// Keys in the render context shadow globals of the same name.
+    pub fn add_global(&mut self, name: &str, value: impl Into<Value>) {
+        self.globals
+            .insert(name.to_string(), Global::Value(value.into()));
+    }
+
+    /// Like `add_global`, but the value is computed when a render first looks it up
+    ///
+    /// Example:
+    ///     templates.add_global_fn("current_year", || json!(current_year()));
+    pub fn add_global_fn<F>(&mut self, name: &str, f: F)
+    where
+        F: Fn() -> Value + Send + Sync + RefUnwindSafe + 'static,
+    {
+        self.globals
+            .insert(name.to_string(), Global::Function(Arc::new(f)));
+    }
+
+    /// Register a function callable from expressions, like `{{ asset('app.css') }}`
+    /// or `@if (has_role(user, 'admin'))`.
+    ///
+    /// `arity` is the number of positional arguments, checked by `validate`. Named arguments
+    /// like `url_for('user', id=user.id)` are passed in the map.
+    ///
+    /// The function runs each time a render reaches the call. When an async render renders a
+    /// block again after a resolver finished, calls that were made before are replayed.
+    ///
+    /// Example:
+    ///     templates.add_function("upper", 1..=1, |args, _| {
+    ///         Ok::<_, String>(json!(args[0].as_str().unwrap_or_default().to_uppercase()))
+    ///     });
+    pub fn add_function<R, F, E>(&mut self, name: &str, arity: R, f: F)
+    where
+        R: RangeBounds<usize>,
+        F: Fn(&[Value], &Map<String, Value>) -> Result<Value, E>
+            + Send
+            + Sync
+            + RefUnwindSafe
+            + 'static,
+        E: Display,
+    {
+        self.functions
+            .insert(name.to_string(), Function::new(arity, f));
+    }
+
+    /// Choose between strict and JavaScript-like loose comparisons in conditions
+    pub fn set_comparison(&mut self, comparison: Comparison) {
+        self.comparison = comparison;
+    }
+
+    pub(crate) fn comparison(&self) -> Comparison {
+        self.comparison
+    }
+
+    /// In strict mode `try_render` also fails on likely mistakes in the data,
+    /// like two `@for` items with the same `track` key
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    pub(crate) fn strict(&self) -> bool {
+        self.strict
+    }
+
+    /// In HTML mode the parser follows the markup like Angular does. Braces in attribute values,
+    /// comments, `<script>` and `<style>` don't open or close blocks, and `validate` reports
+    /// elements that are not closed in the block they are opened in.
+    ///
+    /// Applies to templates loaded after the call.
+    pub fn set_html_mode(&mut self, html: bool) {
+        self.parse_options.html = html;
+    }
+
+    /// With `false`, whitespace between tags and blocks is removed and other runs of whitespace
+    /// become a single space, like Angular's `preserveWhitespaces: false`. `<pre>`, `<textarea>`,
+    /// `<script>` and `<style>` are left as they are. The default is `true`.
+    ///
+    /// Applies to templates loaded after the call.
+    pub fn set_preserve_whitespace(&mut self, preserve: bool) {
+        self.parse_options.collapse_whitespace = !preserve;
+    }
+
+    /// Minify the markup of templates when they are loaded: whitespace is collapsed like with
+    /// `set_preserve_whitespace(false)`, comments are left out and quotes that are not needed
+    /// are dropped from attribute values. Rendering costs nothing extra.
+    ///
+    /// Applies to templates loaded after the call.
+    pub fn set_minify(&mut self, minify: bool) {
+        self.parse_options.minify = minify;
+    }
+
+    /// Set the output mode of a template, instead of the one picked by its extension
+    pub fn set_output_mode(&mut self, key: &str, mode: OutputMode) {
+        self.output_modes.insert(normalize_key(key), mode);
+    }
+
+    pub(crate) fn output_mode(&self, key: &str) -> OutputMode {
+        let key = normalize_key(key);
+        self.output_modes
+            .get(&key)
+            .copied()
+            .unwrap_or_else(|| OutputMode::from_key(&key))
+    }
+
+    /// Leave `<!-- -->` comments out of the output. Conditional comments like `<!--[if IE]>` are kept.
+    ///
+    /// Applies to templates loaded after the call.
+    pub fn set_strip_comments(&mut self, strip: bool) {
+        self.parse_options.strip_comments = strip;
+    }
+
+    /// Angular-only attributes like `(click)`, `[(ngModel)]`, `#form` and `i18n` are left out of
+    /// the output. This renames one instead and keeps its value, like `(click)` to `hx-post`.
+    ///
+    /// Applies to templates loaded after the call.
+    pub fn map_attribute(&mut self, angular: &str, html: &str) {
+        self.parse_options
+            .attribute_map
+            .insert(angular.to_string(), html.to_string());
+    }
+
+    pub(crate) fn function(&self, name: &str) -> Option<&Function> {
+        self.functions.get(name)
+    }
+
+    pub(crate) fn global(&self, name: &str) -> Option<&Global> {
+        self.globals.get(name)
+    }
+
+    pub(crate) fn global_functions(&self) -> impl Iterator<Item = &str> {
+        self.globals
+            .iter()
+            .filter(|(_, global)| matches!(global, Global::Function(_)))
+            .map(|(name, _)| name.as_str())
     }
 
     pub fn get(&self, key: &str) -> Option<&Vec<Node>> {
@@ -70,13 +267,120 @@ impl Templates {
     }
 
     pub fn render(&self, path: &str, ctx: &Value) -> String {
+        let mut out = String::new();
+        // Writing to a String never fails
+        let _ = self.render_fmt(&mut out, path, ctx);
+        out
+    }
+
+    /// Render, returning an error if the template is missing or a function call fails.
+    ///
+    /// `render` writes nothing for failed calls instead.
+    pub fn try_render(&self, path: &str, ctx: &Value) -> Result<String, Error> {
+        let nodes = self
+            .get(path)
+            .ok_or_else(|| Error::MissingTemplate(path.to_string()))?;
+
+        let state = RenderState::new(self);
+        let mut out = String::new();
+        // Writing to a String never fails
+        let _ = self.render_with_state(nodes, path, ctx, &state, &mut out);
+
+        match state.take_error() {
+            Some(error) => Err(error),
+            None => Ok(out),
+        }
+    }
+
+    /// Render into a `std::io::Write` sink, like a file or socket, without building the output in memory.
+    ///
+    /// Output is written in many small pieces, so wrap unbuffered sinks in a `BufWriter`.
+    pub fn render_to<W: io::Write + ?Sized>(
+        &self,
+        writer: &mut W,
+        path: &str,
+        ctx: &Value,
+    ) -> io::Result<()> {
+        let mut adapter = IoWriter {
+            inner: writer,
+            error: None,
+        };
+        self.render_fmt(&mut adapter, path, ctx)
+            .map_err(|_| adapter.into_error())
+    }
+
+    /// Render into a `std::fmt::Write` sink
+    pub fn render_fmt<W: fmt::Write + ?Sized>(
+        &self,
+        writer: &mut W,
+        path: &str,
+        ctx: &Value,
+    ) -> fmt::Result {
+        let mut writer = FmtWriter(writer);
         if let Some(nodes) = self.get(path) {
-            let mut ctx_stack = ContextStack::new(ctx);
-            render_nodes(nodes, &mut ctx_stack, self, None)
+            self.render_with_state(nodes, path, ctx, &RenderState::new(self), &mut writer)
         } else {
-            format!("<!-- Missing template: {} -->", path)
+            self.output_mode(path)
+                .write_missing("template", path, &mut writer)
         }
     }
+
+    /// Render the nodes of the template at `path`, keeping errors in `state`
+    fn render_with_state(
+        &self,
+        nodes: &[Node],
+        path: &str,
+        ctx: &Value,
+        state: &RenderState,
+        out: &mut dyn fmt::Write,
+    ) -> fmt::Result {
+        let mut ctx_stack =
+            ContextStack::new(ctx, Env::new(self, state)).with_mode(self.output_mode(path));
+        render_nodes(nodes, &mut ctx_stack, self, None, out)
+    }
+
+    /// Render with any `Serialize` value as context.
+    ///
+    /// Top-level fields the template does not refer to are skipped. A field it does refer to
+    /// is converted whole, even when the template only uses part of it.
+    pub fn render_serialize<T: Serialize + ?Sized>(&self, path: &str, ctx: &T) -> String {
+        let ctx = match self.get(path) {
+            Some(nodes) => pick_fields(ctx, &root_keys(nodes)),
+            None => Value::Null,
+        };
+        self.render(path, &ctx)
+    }
+}
+
+/// Lets unsized writers be used as `&mut dyn fmt::Write`
+struct FmtWriter<'a, W: fmt::Write + ?Sized>(&'a mut W);
+
+impl<W: fmt::Write + ?Sized> fmt::Write for FmtWriter<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.write_str(s)
+    }
+}
+
+/// Adapts an `io::Write` to `fmt::Write`, keeping the io error that `fmt::Error` cannot carry
+struct IoWriter<'a, W: io::Write + ?Sized> {
+    inner: &'a mut W,
+    error: Option<io::Error>,
+}
+
+impl<W: io::Write + ?Sized> IoWriter<'_, W> {
+    fn into_error(self) -> io::Error {
+        self.error
+            .unwrap_or_else(|| io::Error::other("formatter error"))
+    }
+}
+
+impl<W: io::Write + ?Sized> fmt::Write for IoWriter<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.inner.write_all(s.as_bytes()).map_err(|e| {
+            self.error = Some(e);
+            fmt::Error
+        })
+    }
 }
 
 fn strip_base(base_dir: &str, absolute_path: &str) -> String {
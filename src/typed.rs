@@ -0,0 +1,259 @@
+//! Support for templates compiled with `#[derive(Template)]`.
+//!
+//! The derive macro turns a template into Rust code against the fields of a struct.
+//! Interpolated values implement [`RenderValue`] and `@if` conditions use [`Truthy`],
+//! which follow the same rules as the runtime engine. `in` and `contains` use [`Contains`].
+
+use std::borrow::{Borrow, Cow};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Write};
+use std::hash::{BuildHasher, Hash};
+
+use serde_json::Value;
+
+/// A template compiled into Rust code
+pub trait Template {
+    fn render_to(&self, out: &mut dyn Write) -> fmt::Result;
+
+    fn render(&self) -> String {
+        let mut out = String::new();
+        // Writing to a String never fails
+        let _ = self.render_to(&mut out);
+        out
+    }
+}
+
+/// Values that can be written by `{{ value }}`
+pub trait RenderValue {
+    fn render_value(&self, out: &mut dyn Write) -> fmt::Result;
+}
+
+/// Values that can be used as an `@if` condition
+pub trait Truthy {
+    fn truthy(&self) -> bool;
+}
+
+// Used by the code `#[derive(Template)]` generates
+#[doc(hidden)]
+pub use crate::helpers::{
+    ClassList, EscapeAttr, EscapeMarkdown, EscapeXml, StepRange, StyleList, range, write_json,
+};
+
+/// Values that can be searched with `needle in haystack` or `haystack contains needle`
+pub trait Contains<N: ?Sized> {
+    fn contains_value(&self, needle: &N) -> bool;
+}
+
+macro_rules! display_values {
+    ($($t:ty),*) => {
+        $(
+            impl RenderValue for $t {
+                fn render_value(&self, out: &mut dyn Write) -> fmt::Result {
+                    write!(out, "{}", self)
+                }
+            }
+        )*
+    };
+}
+
+display_values!(
+    str, String, char, bool, i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize
+);
+
+// Floats are written like the runtime writes JSON numbers, so `1.0` stays `1.0` and NaN is left out
+macro_rules! float_values {
+    ($($t:ty),*) => {
+        $(
+            impl RenderValue for $t {
+                fn render_value(&self, out: &mut dyn Write) -> fmt::Result {
+                    crate::output::write_plain(&Value::from(*self), out)
+                }
+            }
+        )*
+    };
+}
+
+float_values!(f32, f64);
+
+impl RenderValue for Cow<'_, str> {
+    fn render_value(&self, out: &mut dyn Write) -> fmt::Result {
+        out.write_str(self)
+    }
+}
+
+impl<T: RenderValue> RenderValue for Option<T> {
+    fn render_value(&self, out: &mut dyn Write) -> fmt::Result {
+        match self {
+            Some(v) => v.render_value(out),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<T: RenderValue + ?Sized> RenderValue for &T {
+    fn render_value(&self, out: &mut dyn Write) -> fmt::Result {
+        (**self).render_value(out)
+    }
+}
+
+impl<T: RenderValue + ?Sized> RenderValue for Box<T> {
+    fn render_value(&self, out: &mut dyn Write) -> fmt::Result {
+        (**self).render_value(out)
+    }
+}
+
+impl RenderValue for Value {
+    fn render_value(&self, out: &mut dyn Write) -> fmt::Result {
+        match self {
+            Value::String(s) => out.write_str(s),
+            Value::Null => Ok(()),
+            other => write!(out, "{}", other),
+        }
+    }
+}
+
+impl Truthy for bool {
+    fn truthy(&self) -> bool {
+        *self
+    }
+}
+
+macro_rules! number_truthiness {
+    ($($t:ty),*) => {
+        $(
+            impl Truthy for $t {
+                fn truthy(&self) -> bool {
+                    *self != (0 as $t)
+                }
+            }
+        )*
+    };
+}
+
+number_truthiness!(
+    i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize, f32, f64
+);
+
+impl Truthy for str {
+    fn truthy(&self) -> bool {
+        !self.is_empty()
+    }
+}
+
+impl Truthy for String {
+    fn truthy(&self) -> bool {
+        !self.is_empty()
+    }
+}
+
+impl Truthy for Cow<'_, str> {
+    fn truthy(&self) -> bool {
+        !self.is_empty()
+    }
+}
+
+impl<T: Truthy> Truthy for Option<T> {
+    fn truthy(&self) -> bool {
+        self.as_ref().is_some_and(Truthy::truthy)
+    }
+}
+
+impl<T> Truthy for [T] {
+    fn truthy(&self) -> bool {
+        !self.is_empty()
+    }
+}
+
+impl<T> Truthy for Vec<T> {
+    fn truthy(&self) -> bool {
+        !self.is_empty()
+    }
+}
+
+impl<K, V, S> Truthy for HashMap<K, V, S> {
+    fn truthy(&self) -> bool {
+        !self.is_empty()
+    }
+}
+
+impl<K, V> Truthy for BTreeMap<K, V> {
+    fn truthy(&self) -> bool {
+        !self.is_empty()
+    }
+}
+
+impl<T: Truthy + ?Sized> Truthy for &T {
+    fn truthy(&self) -> bool {
+        (**self).truthy()
+    }
+}
+
+impl<T: Truthy + ?Sized> Truthy for Box<T> {
+    fn truthy(&self) -> bool {
+        (**self).truthy()
+    }
+}
+
+impl Truthy for Value {
+    fn truthy(&self) -> bool {
+        crate::compare::is_truthy(self)
+    }
+}
+
+impl<N: AsRef<str> + ?Sized> Contains<N> for str {
+    fn contains_value(&self, needle: &N) -> bool {
+        self.contains(needle.as_ref())
+    }
+}
+
+impl<N: AsRef<str> + ?Sized> Contains<N> for String {
+    fn contains_value(&self, needle: &N) -> bool {
+        self.contains(needle.as_ref())
+    }
+}
+
+impl<T: PartialEq<N>, N> Contains<N> for [T] {
+    fn contains_value(&self, needle: &N) -> bool {
+        self.iter().any(|item| item == needle)
+    }
+}
+
+impl<T: PartialEq<N>, N> Contains<N> for Vec<T> {
+    fn contains_value(&self, needle: &N) -> bool {
+        self.as_slice().contains_value(needle)
+    }
+}
+
+impl<K, V, S, N> Contains<N> for HashMap<K, V, S>
+where
+    K: Borrow<str> + Hash + Eq,
+    S: BuildHasher,
+    N: AsRef<str> + ?Sized,
+{
+    fn contains_value(&self, needle: &N) -> bool {
+        self.contains_key(needle.as_ref())
+    }
+}
+
+impl<K, V, N> Contains<N> for BTreeMap<K, V>
+where
+    K: Borrow<str> + Ord,
+    N: AsRef<str> + ?Sized,
+{
+    fn contains_value(&self, needle: &N) -> bool {
+        self.contains_key(needle.as_ref())
+    }
+}
+
+impl<T: Contains<N> + ?Sized, N: ?Sized> Contains<N> for &T {
+    fn contains_value(&self, needle: &N) -> bool {
+        (**self).contains_value(needle)
+    }
+}
+
+impl<T: Contains<N>, N: ?Sized> Contains<N> for Option<T> {
+    fn contains_value(&self, needle: &N) -> bool {
+        self.as_ref()
+            .is_some_and(|haystack| haystack.contains_value(needle))
+    }
+}
@@ -0,0 +1,382 @@
+#![cfg(feature = "macros")]
+
+use std::collections::HashMap;
+
+use serde_json::json;
+use skabelon::{Template, Templates};
+
+struct Item {
+    name: String,
+    price: u32,
+}
+
+#[derive(Template)]
+#[template(path = "tests/templates/typed/page.html", root = "tests/templates")]
+struct Page {
+    title: String,
+    items: Vec<Item>,
+}
+
+#[test]
+fn typed_template_with_partials() {
+    let page = Page {
+        title: "Shop".into(),
+        items: vec![
+            Item {
+                name: "Apple".into(),
+                price: 5,
+            },
+            Item {
+                name: "Melon".into(),
+                price: 12,
+            },
+        ],
+    };
+
+    let expected = "<h1>Shop</h1>\n<div>#0 Apple</div>\n<div>#1 Melon (expensive)</div>\n\n";
+
+    assert_eq!(page.render(), expected);
+}
+
+#[test]
+fn typed_matches_runtime() {
+    let mut templates = Templates::new();
+    templates.load_glob("tests/templates/**/*.html");
+
+    let ctx = json!({
+        "title": "Shop",
+        "items": [{"name": "Apple", "price": 5}, {"name": "Melon", "price": 12}]
+    });
+
+    let page = Page {
+        title: "Shop".into(),
+        items: vec![
+            Item {
+                name: "Apple".into(),
+                price: 5,
+            },
+            Item {
+                name: "Melon".into(),
+                price: 12,
+            },
+        ],
+    };
+
+    assert_eq!(page.render(), templates.render("typed/page.html", &ctx));
+}
+
+#[derive(Template)]
+#[template(
+    source = "@if (show && name == 'Bob') {Hi {{ name }}} @else if (!tags) {no tags} @else {@for (tag in tags) {[{{tag}}]}}"
+)]
+struct Greeting<'a> {
+    show: bool,
+    name: &'a str,
+    tags: Vec<&'a str>,
+}
+
+#[test]
+fn typed_conditions() {
+    let greeting = Greeting {
+        show: true,
+        name: "Bob",
+        tags: vec![],
+    };
+    assert_eq!(greeting.render(), "Hi Bob");
+
+    let greeting = Greeting {
+        show: false,
+        name: "Bob",
+        tags: vec![],
+    };
+    assert_eq!(greeting.render(), "no tags");
+
+    let greeting = Greeting {
+        show: false,
+        name: "Bob",
+        tags: vec!["a", "b"],
+    };
+    assert_eq!(greeting.render(), "[a][b]");
+}
+
+#[derive(Template)]
+#[template(source = "@for (n in numbers) {@if (n >= 2) {{{ n }}}}{{ missing }}|{{ nested.value }}")]
+struct Numbers {
+    numbers: Vec<i64>,
+    missing: Option<String>,
+    nested: Nested,
+}
+
+struct Nested {
+    value: f64,
+}
+
+#[test]
+fn typed_values() {
+    let numbers = Numbers {
+        numbers: vec![1, 2, 3],
+        missing: None,
+        nested: Nested { value: 1.5 },
+    };
+
+    assert_eq!(numbers.render(), "23|1.5");
+}
+
+#[derive(Template)]
+#[template(source = "{{ whole }} {{ half }} {{ small }} {{ big }}")]
+struct Floats {
+    whole: f64,
+    half: f32,
+    small: f64,
+    big: f64,
+}
+
+#[test]
+fn typed_floats_match_runtime() {
+    let mut templates = Templates::new();
+    templates.load_str("floats", "{{ whole }} {{ half }} {{ small }} {{ big }}");
+
+    let floats = Floats {
+        whole: 1.0,
+        half: 0.5,
+        small: 0.1,
+        big: 1e21,
+    };
+    let ctx = json!({"whole": 1.0, "half": 0.5f32, "small": 0.1, "big": 1e21});
+
+    assert_eq!(floats.render(), templates.render("floats", &ctx));
+    assert_eq!(floats.render(), "1.0 0.5 0.1 1e21");
+}
+
+#[derive(Template)]
+#[template(
+    source = "<a href=\"{{ url_for('user', id) }}\">{{ name }}</a>@if (is_admin()) { (admin)}"
+)]
+struct UserLink {
+    id: u32,
+    name: String,
+    roles: Vec<&'static str>,
+}
+
+impl UserLink {
+    fn url_for(&self, page: &str, id: &u32) -> String {
+        format!("/{}/{}", page, id)
+    }
+
+    fn is_admin(&self) -> bool {
+        self.roles.contains(&"admin")
+    }
+}
+
+#[test]
+fn typed_calls_are_methods() {
+    let link = UserLink {
+        id: 7,
+        name: "Bob".into(),
+        roles: vec!["admin"],
+    };
+
+    assert_eq!(link.render(), "<a href=\"/user/7\">Bob</a> (admin)");
+}
+
+#[derive(Template)]
+#[template(
+    source = "@if ('admin' in roles) {admin}@for (tag in tags) {@if (tag startsWith 'r' && tags contains tag) { {{ tag }}}}@if ('x' not in labels) { no x}"
+)]
+struct Membership {
+    roles: Vec<String>,
+    tags: Vec<&'static str>,
+    labels: HashMap<String, u32>,
+}
+
+#[test]
+fn typed_membership() {
+    let membership = Membership {
+        roles: vec!["admin".into()],
+        tags: vec!["rust", "go"],
+        labels: HashMap::from([("y".to_string(), 1)]),
+    };
+
+    assert_eq!(membership.render(), "admin rust no x");
+}
+
+#[derive(Template)]
+#[template(
+    source = "@for (i in range(1, pages + 1)) {[{{ i }}]}@for (i in range(rating, 0, -1)) {*}@if (pages % 2) { odd}"
+)]
+struct Pagination {
+    pages: u32,
+    rating: usize,
+}
+
+#[test]
+fn typed_ranges() {
+    let pagination = Pagination {
+        pages: 3,
+        rating: 2,
+    };
+
+    assert_eq!(pagination.render(), "[1][2][3]** odd");
+}
+
+struct Post {
+    title: &'static str,
+    published: bool,
+}
+
+#[derive(Template)]
+#[template(source = "@for (p of posts; where p.published; limit max) {{{ index }}:{{ p.title }} }")]
+struct Feed {
+    posts: Vec<Post>,
+    max: usize,
+}
+
+#[test]
+fn typed_filtered_loops() {
+    let post = |title, published| Post { title, published };
+    let feed = Feed {
+        posts: vec![
+            post("a", true),
+            post("b", false),
+            post("c", true),
+            post("d", true),
+        ],
+        max: 2,
+    };
+
+    assert_eq!(feed.render(), "0:a 1:c ");
+}
+
+#[derive(Template)]
+#[template(
+    source = "<input class=\"field\" [value]=\"name\" [disabled]=\"locked\" [attr.data-id]=\"id + 1\">"
+)]
+struct Field {
+    name: &'static str,
+    locked: bool,
+    id: u32,
+}
+
+#[test]
+fn typed_bound_attributes() {
+    let field = Field {
+        name: "a \"b\"",
+        locked: true,
+        id: 1,
+    };
+
+    assert_eq!(
+        field.render(),
+        "<input class=\"field\" value=\"a &quot;b&quot;\" disabled data-id=\"2\">"
+    );
+}
+
+#[derive(Template)]
+#[template(
+    source = "<a class=\"btn {{ kind }}\" [class.active]=\"active\" [ngClass]=\"{'btn': !plain}\" [style.width.px]=\"width\">"
+)]
+struct Button {
+    kind: &'static str,
+    active: bool,
+    plain: bool,
+    width: u32,
+}
+
+#[test]
+fn typed_class_and_style_bindings() {
+    let button = Button {
+        kind: "btn-primary",
+        active: true,
+        plain: true,
+        width: 20,
+    };
+
+    assert_eq!(
+        button.render(),
+        "<a class=\"btn-primary active\" style=\"width: 20px\">"
+    );
+}
+
+#[derive(Template)]
+#[template(
+    source = "<ul><li *ngFor=\"let tag of tags; let last = last\">{{tag}}<ng-container *ngIf=\"!last\">, </ng-container></li></ul>"
+)]
+struct Tags {
+    tags: Vec<&'static str>,
+}
+
+#[test]
+fn typed_structural_directives() {
+    let tags = Tags {
+        tags: vec!["a", "b"],
+    };
+
+    assert_eq!(tags.render(), "<ul><li>a, </li><li>b</li></ul>");
+}
+
+#[derive(Template)]
+#[template(
+    source = "@for (i in range(0, total); limit 3; let c = $count, last = $last) {{{i}}/{{c}}@if (!last) {,}}|@for (n of nums; where n > 1; let c = $count) {{{n}}/{{c}} }"
+)]
+struct Counts {
+    total: i64,
+    nums: Vec<i64>,
+}
+
+#[test]
+fn typed_loop_counts() {
+    let counts = Counts {
+        total: 9_000_000_000_000_000_000,
+        nums: vec![1, 2, 3],
+    };
+
+    assert_eq!(counts.render(), "0/3,1/3,2/3|2/2 3/2 ");
+}
+
+#[derive(Template)]
+#[template(path = "tests/templates/modes/sitemap.xml")]
+struct Sitemap {
+    loc: String,
+    title: String,
+}
+
+#[derive(Template)]
+#[template(path = "tests/templates/modes/data.json")]
+struct Data {
+    title: String,
+    count: u32,
+    tags: Vec<String>,
+    missing: Option<String>,
+}
+
+#[test]
+fn typed_output_modes_match_runtime() {
+    let mut templates = Templates::new();
+    templates.load_str("sitemap.xml", include_str!("templates/modes/sitemap.xml"));
+    templates.load_str("note.md", include_str!("templates/modes/note.md"));
+    templates.load_str("data.json", include_str!("templates/modes/data.json"));
+
+    let sitemap = Sitemap {
+        loc: "/a?b=1&c=<2>".into(),
+        title: "*Tom* & Jerry".into(),
+    };
+    let ctx = json!({"loc": "/a?b=1&c=<2>", "title": "*Tom* & Jerry"});
+    assert_eq!(sitemap.render(), templates.render("sitemap.xml", &ctx));
+    assert_eq!(
+        sitemap.render(),
+        "<url><loc>/a?b=1&amp;c=&lt;2&gt;</loc># \\*Tom\\* & Jerry</url>\n"
+    );
+
+    let data = Data {
+        title: "Say \"hi\"".into(),
+        count: 2,
+        tags: vec!["a".into()],
+        missing: None,
+    };
+    let ctx = json!({"title": "Say \"hi\"", "count": 2, "tags": ["a"]});
+    assert_eq!(data.render(), templates.render("data.json", &ctx));
+    assert_eq!(
+        data.render(),
+        r#"{"title": "Say \"hi\"", "count": 2, "tags": ["a"], "missing": null}"#
+    );
+}
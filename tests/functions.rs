@@ -0,0 +1,167 @@
+use serde_json::{Value, json};
+use skabelon::{Error, Templates};
+
+fn templates() -> Templates {
+    let mut templates = Templates::new();
+    templates.add_function("asset", 1..=1, |args, _| {
+        Ok::<_, String>(json!(format!(
+            "/static/{}?v=3",
+            args[0].as_str().unwrap_or_default()
+        )))
+    });
+    templates.add_function("url_for", 1.., |args, named| {
+        let mut url = format!("/{}", args[0].as_str().unwrap_or_default());
+        for arg in &args[1..] {
+            url.push_str(&format!("/{}", arg));
+        }
+        if let Some(page) = named.get("page") {
+            url.push_str(&format!("?page={}", page));
+        }
+        Ok::<_, String>(json!(url))
+    });
+    templates.add_function("has_role", 2..=2, |args, _| {
+        let roles = args[0]["roles"].as_array().cloned().unwrap_or_default();
+        Ok::<_, String>(json!(roles.contains(&args[1])))
+    });
+    templates.add_function("len", 1..=1, |args, _| match &args[0] {
+        Value::Array(a) => Ok(json!(a.len())),
+        other => Err(format!("can't take the length of {}", other)),
+    });
+    templates
+}
+
+#[test]
+fn calls_in_interpolations() {
+    let mut templates = templates();
+    templates.load_str(
+        "page",
+        "<link href=\"{{ asset('app.css') }}\"><a href=\"{{ url_for('user', user.id, page=2) }}\">{{ url_for('home') }}</a>",
+    );
+
+    assert_eq!(
+        templates.render("page", &json!({"user": {"id": 7}})),
+        "<link href=\"/static/app.css?v=3\"><a href=\"/user/7?page=2\">/home</a>"
+    );
+}
+
+#[test]
+fn calls_in_conditions() {
+    let mut templates = templates();
+    templates.load_str(
+        "page",
+        "@if (has_role(user, 'admin')) {admin}@if (len(items) > 1 && !has_role(user, 'guest')) { many}",
+    );
+
+    let ctx = json!({"user": {"roles": ["admin"]}, "items": [1, 2]});
+    assert_eq!(templates.render("page", &ctx), "admin many");
+
+    let ctx = json!({"user": {"roles": ["guest"]}, "items": [1, 2]});
+    assert_eq!(templates.render("page", &ctx), "");
+}
+
+#[test]
+fn nested_calls_and_quoted_arguments() {
+    let mut templates = templates();
+    templates.load_str("page", "{{ url_for('shop, main', len(items)) }}");
+
+    assert_eq!(
+        templates.render("page", &json!({"items": [1, 2, 3]})),
+        "/shop, main/3"
+    );
+}
+
+#[test]
+fn validate_checks_calls() {
+    let mut templates = templates();
+    templates.load_str(
+        "page",
+        "{{ asset() }}@if (missing(user)) {}{{ url_for('a', id=len(1, 2)) }}",
+    );
+
+    let diagnostics: Vec<String> = templates
+        .validate()
+        .unwrap_err()
+        .iter()
+        .map(|d| d.to_string())
+        .collect();
+
+    assert_eq!(
+        diagnostics,
+        vec![
+            "page: `asset()`: expects 1 argument, got 0",
+            "page: `missing()`: unknown function",
+            "page: `len()`: expects 1 argument, got 2",
+        ]
+    );
+}
+
+#[test]
+fn errors_surface_through_try_render() {
+    let mut templates = templates();
+    templates.load_str("page", "<p>{{ len(name) }}</p>");
+
+    let ctx = json!({"name": "Bob"});
+    assert_eq!(
+        templates.try_render("page", &ctx),
+        Err(Error::Function {
+            name: "len".into(),
+            message: "can't take the length of \"Bob\"".into(),
+        })
+    );
+    assert_eq!(templates.render("page", &ctx), "<p></p>");
+
+    assert_eq!(
+        templates.try_render("missing", &ctx),
+        Err(Error::MissingTemplate("missing".into()))
+    );
+}
+
+#[test]
+fn serialize_picks_call_arguments() {
+    let mut templates = templates();
+    templates.load_str("page", "{{ url_for('user', user.id) }}");
+
+    #[derive(serde::Serialize)]
+    struct User {
+        id: u32,
+    }
+
+    #[derive(serde::Serialize)]
+    struct Ctx {
+        user: User,
+    }
+
+    assert_eq!(
+        templates.render_serialize(
+            "page",
+            &Ctx {
+                user: User { id: 4 }
+            }
+        ),
+        "/user/4"
+    );
+}
+
+#[test]
+fn templates_with_functions_are_unwind_safe() {
+    let mut templates = templates();
+    templates.add_global_fn("year", || json!(2026));
+    templates.load_str("test", "{{ asset('app.css') }} {{ year }}");
+
+    let html = std::panic::catch_unwind(|| templates.render("test", &json!({})));
+
+    assert_eq!(html.unwrap(), "/static/app.css?v=3 2026");
+}
+
+#[test]
+fn functions_are_called_every_time() {
+    let counter = std::sync::atomic::AtomicUsize::new(0);
+    let mut templates = Templates::new();
+    templates.add_function("next", 0..=0, move |_, _| {
+        let n = counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        Ok::<_, String>(json!(n))
+    });
+    templates.load_str("test", "@for (i in range(3)) {[{{ next() }}]}");
+
+    assert_eq!(templates.render("test", &json!({})), "[0][1][2]");
+}
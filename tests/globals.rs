@@ -0,0 +1,65 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use serde_json::json;
+use skabelon::Templates;
+
+#[test]
+fn globals_are_available_everywhere() {
+    let mut templates = Templates::new();
+    templates.add_global("site_name", "Skabelon");
+    templates.add_global("nav", json!(["Home", "About"]));
+    templates.load_str(
+        "page",
+        "<title>{{site_name}}</title>@for(item in nav) {<a>{{item}}</a>}@defer(footer) {}",
+    );
+    templates.load_str("footer", "<footer>{{site_name}}</footer>");
+
+    assert_eq!(
+        templates.render("page", &json!({})),
+        "<title>Skabelon</title><a>Home</a><a>About</a><footer>Skabelon</footer>"
+    );
+}
+
+#[test]
+fn context_shadows_globals() {
+    let mut templates = Templates::new();
+    templates.add_global("title", "Default");
+    templates.load_str("page", "{{title}} @for(title in titles) {{{title}}}");
+
+    assert_eq!(
+        templates.render("page", &json!({"title": "Page", "titles": ["Loop"]})),
+        "Page Loop"
+    );
+    assert_eq!(templates.render("page", &json!({"titles": []})), "Default ");
+}
+
+#[test]
+fn global_functions_are_computed_once_per_render() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+
+    let mut templates = Templates::new();
+    templates.add_global_fn("year", move || {
+        counter.fetch_add(1, Ordering::SeqCst);
+        json!(2026)
+    });
+    templates.add_global_fn("unused", || panic!("not looked up"));
+    templates.load_str("page", "{{year}} @if(year > 2000) {{{year}}}");
+
+    assert_eq!(templates.render("page", &json!({})), "2026 2026");
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+    templates.render("page", &json!({}));
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn later_globals_replace_earlier() {
+    let mut templates = Templates::new();
+    templates.add_global_fn("version", || json!("fn"));
+    templates.add_global("version", 3);
+    templates.load_str("page", "v{{version}}");
+
+    assert_eq!(templates.render("page", &json!({})), "v3");
+}
@@ -0,0 +1,56 @@
+#![cfg(feature = "macros")]
+
+use serde_json::json;
+
+#[test]
+fn embed_glob() {
+    let templates = skabelon::embed!("tests/templates/*.html");
+
+    let output = templates.render("partial2.html", &json!({}));
+    let expected = include_str!("templates/partial2.html");
+
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn embed_keys_are_relative() {
+    let templates = skabelon::embed!("tests/templates/**/*.html");
+
+    assert!(templates.get("main.html").is_some());
+    assert!(templates.get("partial1.html").is_some());
+    assert!(templates.get("tests/templates/main.html").is_none());
+}
+
+skabelon::validate!("tests/templates/**/*.html");
+
+#[test]
+fn validate_in_function() {
+    skabelon::validate!("tests/templates/*.html");
+}
+
+#[test]
+fn embed_with_parse_options() {
+    let templates = skabelon::embed!(
+        "tests/templates/*.html",
+        minify,
+        preserve_whitespace = false
+    );
+
+    let mut expected = skabelon::Templates::new();
+    expected.set_minify(true);
+    expected.set_preserve_whitespace(false);
+    expected.load_glob(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/templates/*.html"
+    ));
+
+    for key in ["main.html", "partial2.html"] {
+        assert_eq!(templates.get(key), expected.get(key));
+        assert_ne!(
+            templates.get(key),
+            skabelon::embed!("tests/templates/*.html").get(key)
+        );
+    }
+}
+
+skabelon::validate!("tests/templates/**/*.html", html_mode);
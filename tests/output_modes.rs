@@ -0,0 +1,65 @@
+use serde_json::json;
+use skabelon::{OutputMode, Templates};
+
+#[test]
+fn modes_follow_the_extension() {
+    let mut templates = Templates::new();
+    templates.load_str("feed.xml", "<title>{{title}}</title>@defer(missing.xml) {}");
+    templates.load_str("mail.txt", "Hi {{name}}, {{title}}@defer(missing.txt) {}");
+    templates.load_str("notes.md", "# {{title}}");
+    templates.load_str("page.html", "<h1>{{title}}</h1>@defer(missing.html) {}");
+
+    let ctx = json!({"title": "Tom & <Jerry> *1*", "name": "Ann"});
+    assert_eq!(
+        templates.render("feed.xml", &ctx),
+        "<title>Tom &amp; &lt;Jerry&gt; *1*</title><!-- Missing defer: missing.xml -->"
+    );
+    assert_eq!(
+        templates.render("mail.txt", &ctx),
+        "Hi Ann, Tom & <Jerry> *1*"
+    );
+    assert_eq!(
+        templates.render("notes.md", &ctx),
+        "# Tom & \\<Jerry\\> \\*1\\*"
+    );
+    assert_eq!(
+        templates.render("page.html", &ctx),
+        "<h1>Tom & <Jerry> *1*</h1><!-- Missing defer: missing.html -->"
+    );
+    assert_eq!(templates.render("missing.txt", &ctx), "");
+}
+
+#[test]
+fn json_mode_writes_literals() {
+    let mut templates = Templates::new();
+    templates.load_str(
+        "config.json",
+        "{\"name\": {{name}}, \"port\": {{port}}, \"tags\": {{tags}}, \"missing\": {{missing}}, \"items\": [@for (item in items; let last = $last) {{{item.id}}@if (!last) {, }}]}",
+    );
+
+    let ctx =
+        json!({"name": "a \"b\"", "port": 80, "tags": ["x"], "items": [{"id": 1}, {"id": "2"}]});
+    let output = templates.render("config.json", &ctx);
+
+    assert_eq!(
+        output,
+        "{\"name\": \"a \\\"b\\\"\", \"port\": 80, \"tags\": [\"x\"], \"missing\": null, \"items\": [1, \"2\"]}"
+    );
+    assert!(serde_json::from_str::<serde_json::Value>(&output).is_ok());
+}
+
+#[test]
+fn explicit_modes_and_partials() {
+    let mut templates = Templates::new();
+    templates.set_output_mode("sitemap", OutputMode::Xml);
+    templates.load_str(
+        "sitemap",
+        "<loc>{{url}}</loc>@defer(entry.json; url=url) {}",
+    );
+    templates.load_str("entry.json", "{{url}}");
+
+    assert_eq!(
+        templates.render("sitemap", &json!({"url": "/a?b=1&c=2"})),
+        "<loc>/a?b=1&amp;c=2</loc>\"/a?b=1&c=2\""
+    );
+}
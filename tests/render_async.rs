@@ -0,0 +1,174 @@
+#![cfg(feature = "async")]
+
+use std::future::IntoFuture;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use futures::executor::block_on;
+use serde_json::{Value, json};
+use skabelon::{RenderAsync, Templates};
+
+fn render(render: RenderAsync) -> String {
+    block_on(render.into_future())
+}
+
+fn counting(
+    calls: &Arc<AtomicUsize>,
+    value: Value,
+) -> impl Fn() -> futures::future::Ready<Result<Value, String>> + Send + Sync + 'static {
+    let calls = calls.clone();
+    move || {
+        calls.fetch_add(1, Ordering::SeqCst);
+        futures::future::ready(Ok(value.clone()))
+    }
+}
+
+#[test]
+fn resolves_used_keys() {
+    let mut templates = Templates::new();
+    templates.load_str(
+        "page",
+        "<h1>{{title}}</h1>@for(item in recommendations) {<li>{{item}}</li>}",
+    );
+
+    let ctx = json!({"title": "Shop"});
+    let html = render(
+        templates
+            .render_async("page", &ctx)
+            .with_resolver("recommendations", || async {
+                Ok::<_, String>(json!(["A", "B"]))
+            }),
+    );
+
+    assert_eq!(html, "<h1>Shop</h1><li>A</li><li>B</li>");
+}
+
+#[test]
+fn resolvers_are_lazy_and_memoised() {
+    let mut templates = Templates::new();
+    templates.load_str(
+        "page",
+        "@if(show) {{{hidden}}}{{user.name}} {{user.email}}@defer(card; user=user) {}",
+    );
+    templates.load_str("card", "<b>{{user.name}}</b>");
+
+    let hidden = Arc::new(AtomicUsize::new(0));
+    let user = Arc::new(AtomicUsize::new(0));
+
+    let ctx = json!({"show": false});
+    let html = render(
+        templates
+            .render_async("page", &ctx)
+            .with_resolver("hidden", counting(&hidden, json!("secret")))
+            .with_resolver(
+                "user",
+                counting(&user, json!({"name": "Bob", "email": "bob@example.com"})),
+            ),
+    );
+
+    assert_eq!(html, "Bob bob@example.com<b>Bob</b>");
+    assert_eq!(hidden.load(Ordering::SeqCst), 0);
+    assert_eq!(user.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn context_takes_precedence() {
+    let mut templates = Templates::new();
+    templates.load_str("page", "{{name}}");
+
+    let calls = Arc::new(AtomicUsize::new(0));
+
+    let ctx = json!({"name": "from context"});
+    let html = render(
+        templates
+            .render_async("page", &ctx)
+            .with_resolver("name", counting(&calls, json!("from resolver"))),
+    );
+
+    assert_eq!(html, "from context");
+    assert_eq!(calls.load(Ordering::SeqCst), 0);
+}
+
+#[test]
+fn failed_resolver_renders_as_missing() {
+    let mut templates = Templates::new();
+    templates.load_str("page", "a{{value}}b @if(value) {shown} @else {hidden}");
+
+    let ctx = json!({});
+    let html = render(
+        templates
+            .render_async("page", &ctx)
+            .with_resolver("value", || async { Err::<Value, _>("timeout") }),
+    );
+
+    assert_eq!(html, "ab hidden");
+}
+
+#[test]
+fn resolvers_are_awaited_together() {
+    let mut templates = Templates::new();
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+    templates.add_function("shout", 1..=1, move |args, _| {
+        counter.fetch_add(1, Ordering::SeqCst);
+        Ok::<_, String>(json!(args[0].as_str().unwrap_or_default().to_uppercase()))
+    });
+    templates.load_str(
+        "page",
+        "{{ shout('hi') }} {{a}} {{b}} @if (c) {{{ shout('c') }}}",
+    );
+
+    // `a` only finishes once `b` has started, so awaiting one at a time would never finish
+    let (sender, receiver) = futures::channel::oneshot::channel::<()>();
+    let receiver = std::sync::Mutex::new(Some(receiver));
+    let sender = std::sync::Mutex::new(Some(sender));
+
+    let ctx = json!({});
+    let html = render(
+        templates
+            .render_async("page", &ctx)
+            .with_resolver("a", move || {
+                let receiver = receiver.lock().unwrap().take();
+                async move {
+                    if let Some(receiver) = receiver {
+                        let _ = receiver.await;
+                    }
+                    Ok::<_, String>(json!("A"))
+                }
+            })
+            .with_resolver("b", move || {
+                if let Some(sender) = sender.lock().unwrap().take() {
+                    let _ = sender.send(());
+                }
+                async { Ok::<_, String>(json!("B")) }
+            })
+            .with_resolver("c", || async { Ok::<_, String>(json!(true)) }),
+    );
+
+    assert_eq!(html, "HI A B C");
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn calls_are_replayed_when_a_node_is_rendered_again() {
+    let mut templates = Templates::new();
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+    templates.add_function("next", 0..=0, move |_, _| {
+        Ok::<_, String>(json!(counter.fetch_add(1, Ordering::SeqCst)))
+    });
+    templates.load_str(
+        "page",
+        "@if (true) {{{ next() }} {{a}} {{ next() }}}|{{ next() }}",
+    );
+
+    let ctx = json!({});
+    let html = render(
+        templates
+            .render_async("page", &ctx)
+            .with_resolver("a", || async { Ok::<_, String>(json!("A")) }),
+    );
+
+    assert_eq!(html, "0 A 1|2");
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+}
@@ -0,0 +1,76 @@
+use std::io;
+
+use serde_json::json;
+use skabelon::Templates;
+
+#[test]
+fn render_to_io() {
+    let mut templates = Templates::new();
+    templates.load_str(
+        "parent",
+        "<h1>{{title}}</h1>@defer(partial) {<p>{{title}}</p>}",
+    );
+    templates.load_str("partial", "<div>{{ content }}</div>");
+
+    let ctx = json!({"title": "Hello"});
+
+    let mut out: Vec<u8> = Vec::new();
+    templates.render_to(&mut out, "parent", &ctx).unwrap();
+
+    let expected = "<h1>Hello</h1><div><p>Hello</p></div>";
+
+    assert_eq!(String::from_utf8(out).unwrap(), expected);
+    assert_eq!(templates.render("parent", &ctx), expected);
+}
+
+#[test]
+fn render_fmt() {
+    let mut templates = Templates::new();
+    templates.load_str("test", "@for(item in items) {{{item}}}");
+
+    let mut out = String::from("items: ");
+    templates
+        .render_fmt(&mut out, "test", &json!({"items": [1, 2, 3]}))
+        .unwrap();
+
+    assert_eq!(out, "items: 123");
+}
+
+#[test]
+fn missing_template() {
+    let templates = Templates::new();
+
+    let mut out: Vec<u8> = Vec::new();
+    templates
+        .render_to(&mut out, "missing", Default::default())
+        .unwrap();
+
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "<!-- Missing template: missing -->"
+    );
+}
+
+struct Closed;
+
+impl io::Write for Closed {
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        Err(io::Error::new(io::ErrorKind::BrokenPipe, "closed"))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn io_errors_are_returned() {
+    let mut templates = Templates::new();
+    templates.load_str("test", "hello");
+
+    let error = templates
+        .render_to(&mut Closed, "test", Default::default())
+        .unwrap_err();
+
+    assert_eq!(error.kind(), io::ErrorKind::BrokenPipe);
+}
@@ -0,0 +1,102 @@
+use std::collections::BTreeMap;
+
+use serde::{Serialize, Serializer};
+use serde_json::json;
+use skabelon::Templates;
+
+#[derive(Serialize)]
+struct Page {
+    title: String,
+    items: Vec<Item>,
+    show: bool,
+}
+
+#[derive(Serialize)]
+struct Item {
+    name: String,
+}
+
+#[test]
+fn render_struct() {
+    let template_str =
+        "<h1>{{title}}</h1>@if(show) {@for(item in items) {<span>{{item.name}}</span>}}";
+
+    let mut templates = Templates::new();
+    templates.load_str("test", template_str);
+
+    let page = Page {
+        title: "Hello".into(),
+        items: vec![Item { name: "A".into() }, Item { name: "B".into() }],
+        show: true,
+    };
+
+    let output = templates.render_serialize("test", &page);
+
+    let expected = "<h1>Hello</h1><span>A</span><span>B</span>";
+
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn render_map() {
+    let template_str = "{{greeting}} {{name}}";
+
+    let mut templates = Templates::new();
+    templates.load_str("test", template_str);
+
+    let mut ctx = BTreeMap::new();
+    ctx.insert("greeting", "Hello");
+    ctx.insert("name", "World");
+
+    let output = templates.render_serialize("test", &ctx);
+
+    assert_eq!(output, "Hello World");
+}
+
+#[test]
+fn same_output_as_json() {
+    let template_str = "{{title}} @defer(partial; name=items[1].name) {}";
+
+    let mut templates = Templates::new();
+    templates.load_str("test", template_str);
+    templates.load_str("partial", "<b>{{name}}</b>");
+
+    let page = Page {
+        title: "Hello".into(),
+        items: vec![Item { name: "A".into() }, Item { name: "B".into() }],
+        show: false,
+    };
+
+    let output = templates.render_serialize("test", &page);
+    let expected = templates.render("test", &json!(page));
+
+    assert_eq!(output, expected);
+    assert_eq!(output, "Hello <b>B</b>");
+}
+
+struct Unused;
+
+impl Serialize for Unused {
+    fn serialize<S: Serializer>(&self, _serializer: S) -> Result<S::Ok, S::Error> {
+        panic!("fields the template does not use are not serialized");
+    }
+}
+
+#[derive(Serialize)]
+struct Partial {
+    used: &'static str,
+    unused: Unused,
+}
+
+#[test]
+fn unused_fields_are_skipped() {
+    let mut templates = Templates::new();
+    templates.load_str("test", "{{used}}");
+
+    let ctx = Partial {
+        used: "hello",
+        unused: Unused,
+    };
+
+    assert_eq!(templates.render_serialize("test", &ctx), "hello");
+}
@@ -0,0 +1,252 @@
+#![cfg(feature = "async")]
+
+use futures::StreamExt;
+use futures::executor::block_on;
+use serde_json::json;
+use skabelon::Templates;
+
+fn collect_chunks(templates: &Templates, path: &str, ctx: &serde_json::Value) -> Vec<String> {
+    block_on(
+        templates
+            .render_stream(path, ctx)
+            .map(|chunk| String::from_utf8(chunk.to_vec()).unwrap())
+            .collect(),
+    )
+}
+
+#[test]
+fn chunks_at_defer() {
+    let mut templates = Templates::new();
+    templates.load_str(
+        "page",
+        "<header>{{title}}</header>@defer(report) {}<footer>@if(show) {bye}</footer>",
+    );
+    templates.load_str(
+        "report",
+        "<table>@for(row in rows) {<tr>{{row}}</tr>}</table>",
+    );
+
+    let ctx = json!({"title": "Report", "show": true});
+
+    let chunks = collect_chunks(&templates, "page", &ctx);
+
+    assert_eq!(
+        chunks,
+        vec![
+            "<header>Report</header>",
+            "<table></table>",
+            "<footer>bye</footer>"
+        ]
+    );
+    assert_eq!(chunks.concat(), templates.render("page", &ctx));
+}
+
+#[test]
+fn single_chunk_without_defer() {
+    let mut templates = Templates::new();
+    templates.load_str("page", "@for(item in items) {<li>{{item}}</li>}");
+
+    let ctx = json!({"items": ["A", "B"]});
+
+    let chunks = collect_chunks(&templates, "page", &ctx);
+
+    assert_eq!(chunks, vec!["<li>A</li><li>B</li>"]);
+}
+
+#[test]
+fn missing_template() {
+    let templates = Templates::new();
+
+    let chunks = collect_chunks(&templates, "missing", &json!({}));
+
+    assert_eq!(chunks, vec!["<!-- Missing template: missing -->"]);
+}
+
+#[test]
+fn owned_stream() {
+    let mut templates = Templates::new();
+    templates.load_str("page", "<h1>{{title}}</h1>@defer(partial) {}");
+    templates.load_str("partial", "<p>partial</p>");
+    let templates = std::sync::Arc::new(templates);
+
+    let stream = templates.render_stream_owned("page", json!({"title": "Hello"}));
+    let chunks: Vec<_> = block_on(stream.collect());
+
+    assert_eq!(chunks, vec!["<h1>Hello</h1>", "<p>partial</p>"]);
+}
+
+/// Resolves after being polled `n` times, to control which resolver finishes first
+async fn yield_times(n: usize) {
+    let mut polls = 0;
+    futures::future::poll_fn(|cx| {
+        if polls == n {
+            std::task::Poll::Ready(())
+        } else {
+            polls += 1;
+            cx.waker().wake_by_ref();
+            std::task::Poll::Pending
+        }
+    })
+    .await
+}
+
+#[test]
+fn out_of_order_defer() {
+    let mut templates = Templates::new();
+    templates.load_str(
+        "page",
+        "<h1>{{title}}</h1>@defer {<ul>@for(r in recs) {<li>{{r}}</li>}</ul>} @placeholder {<p>loading</p>}<footer>end</footer>",
+    );
+
+    let ctx = json!({"title": "Hi"});
+    let stream = templates
+        .render_stream("page", &ctx)
+        .with_resolver("recs", || async { Ok::<_, String>(json!(["a", "b"])) });
+
+    let chunks: Vec<String> = block_on(
+        stream
+            .map(|chunk| String::from_utf8(chunk.to_vec()).unwrap())
+            .collect(),
+    );
+
+    assert_eq!(chunks.len(), 4);
+    assert_eq!(chunks[0], "<h1>Hi</h1>");
+    assert_eq!(
+        chunks[1],
+        "<!--sk-defer:0--><p>loading</p><!--/sk-defer:0-->"
+    );
+    assert_eq!(chunks[2], "<footer>end</footer>");
+    assert!(chunks[3].starts_with("<script>function $sk(i)"));
+    assert!(chunks[3].ends_with(
+        "<template id=\"sk-defer-0\"><ul><li>a</li><li>b</li></ul></template><script>$sk(0)</script>"
+    ));
+}
+
+#[test]
+fn loading_block_is_preferred() {
+    let mut templates = Templates::new();
+    templates.load_str(
+        "page",
+        "@defer(card; name=user.name) {} @placeholder {placeholder} @loading (minimum 500ms) {loading}",
+    );
+    templates.load_str("card", "<b>{{name}}</b>");
+
+    let stream = templates
+        .render_stream("page", Default::default())
+        .with_resolver("user", || async { Ok::<_, String>(json!({"name": "Bob"})) });
+    let chunks: Vec<_> = block_on(stream.collect());
+    let output = chunks.concat();
+    let output = String::from_utf8_lossy(&output);
+
+    assert!(output.starts_with("<!--sk-defer:0-->loading<!--/sk-defer:0-->"));
+    assert!(output.contains("<template id=\"sk-defer-0\"><b>Bob</b></template>"));
+}
+
+#[test]
+fn failed_resolver_renders_error_block() {
+    let mut templates = Templates::new();
+    templates.load_str(
+        "page",
+        "@defer {{{recs}}} @placeholder {wait} @error {<p>Could not load</p>}",
+    );
+
+    let stream = templates
+        .render_stream("page", Default::default())
+        .with_resolver("recs", || async { Err::<serde_json::Value, _>("timeout") });
+    let chunks = block_on(stream.collect::<Vec<_>>()).concat();
+    let output = String::from_utf8_lossy(&chunks);
+
+    assert!(output.contains("<template id=\"sk-defer-0\"><p>Could not load</p></template>"));
+}
+
+#[test]
+fn values_outside_defer_are_awaited_in_place() {
+    let mut templates = Templates::new();
+    templates.load_str("page", "<p>Hello</p><p>{{user.name}}</p>");
+
+    let stream = templates
+        .render_stream("page", Default::default())
+        .with_resolver("user", || async {
+            yield_times(3).await;
+            Ok::<_, String>(json!({"name": "Bob"}))
+        });
+    let chunks: Vec<String> = block_on(
+        stream
+            .map(|chunk| String::from_utf8(chunk.to_vec()).unwrap())
+            .collect(),
+    );
+
+    // What is ready is sent before waiting on the resolver
+    assert_eq!(chunks, vec!["<p>Hello</p><p>", "Bob</p>"]);
+}
+
+#[test]
+fn unused_resolvers_are_not_called() {
+    let mut templates = Templates::new();
+    templates.load_str("page", "@if(false) {{{expensive}}}done");
+
+    let stream = templates
+        .render_stream("page", Default::default())
+        .with_resolver("expensive", || async {
+            panic!("resolver for a hidden branch was called");
+            #[allow(unreachable_code)]
+            Ok::<_, String>(json!(null))
+        });
+    let chunks: Vec<_> = block_on(stream.collect());
+
+    assert_eq!(chunks, vec!["done"]);
+}
+
+#[test]
+fn deferred_blocks_swap_in_as_they_resolve() {
+    let mut templates = Templates::new();
+    templates.load_str(
+        "page",
+        "@defer {{{slow}}} @placeholder {1}@defer {{{fast}}} @placeholder {2}",
+    );
+
+    let stream = templates
+        .render_stream("page", Default::default())
+        .with_resolver("slow", || async {
+            yield_times(10).await;
+            Ok::<_, String>(json!("slow"))
+        })
+        .with_resolver("fast", || async {
+            yield_times(1).await;
+            Ok::<_, String>(json!("fast"))
+        });
+    let chunks: Vec<String> = block_on(
+        stream
+            .map(|chunk| String::from_utf8(chunk.to_vec()).unwrap())
+            .collect(),
+    );
+
+    let fast = chunks.iter().position(|c| c.contains(">fast</template>"));
+    let slow = chunks.iter().position(|c| c.contains(">slow</template>"));
+    assert!(fast.unwrap() < slow.unwrap());
+}
+
+#[test]
+fn deferred_blocks_keep_the_output_mode() {
+    let mut templates = Templates::new();
+    templates.load_str(
+        "feed.xml",
+        "<title>{{title}}</title>@defer {<item>{{item}}</item>} @placeholder {<item/>}",
+    );
+
+    let ctx = json!({"title": "A & B"});
+    let stream = templates
+        .render_stream("feed.xml", &ctx)
+        .with_resolver("item", || async { Ok::<_, String>(json!("<C>")) });
+
+    let chunks: Vec<String> = block_on(
+        stream
+            .map(|chunk| String::from_utf8(chunk.to_vec()).unwrap())
+            .collect(),
+    );
+
+    assert_eq!(chunks[0], "<title>A &amp; B</title>");
+    assert!(chunks.last().unwrap().ends_with(
+        "<template id=\"sk-defer-0\"><item>&lt;C&gt;</item></template><script>$sk(0)</script>"
+    ));
+}
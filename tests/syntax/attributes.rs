@@ -0,0 +1,157 @@
+use serde_json::json;
+use skabelon::Templates;
+
+#[test]
+fn bound_attributes() {
+    let template_str = "<input type=\"text\" [value]=\"user.name\" [disabled]=\"locked\" [readonly]=\"!editable\">";
+
+    let mut templates = Templates::new();
+    templates.load_str("test", template_str);
+
+    let ctx = json!({"user": {"name": "Ann \"A\" <&>"}, "locked": false, "editable": false});
+
+    let output = templates.render("test", &ctx);
+
+    let expected = "<input type=\"text\" value=\"Ann &quot;A&quot; &lt;&amp;>\" readonly>";
+
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn attribute_expressions() {
+    let template_str = "<a href='/users/{{user.id}}' [title]=\"'Profile'\" [attr.aria-label]=\"label\" [attr.data-count]=\"count + 1\" [hidden]=\"count > 2\"/>";
+
+    let mut templates = Templates::new();
+    templates.load_str("test", template_str);
+
+    assert_eq!(
+        templates.render("test", &json!({"user": {"id": 7}, "count": 1})),
+        "<a href=\"/users/7\" title=\"Profile\" data-count=\"2\"/>"
+    );
+    assert_eq!(
+        templates.render(
+            "test",
+            &json!({"user": {"id": 7}, "count": 3, "label": "Bob"})
+        ),
+        "<a href=\"/users/7\" title=\"Profile\" aria-label=\"Bob\" data-count=\"4\" hidden/>"
+    );
+}
+
+#[test]
+fn tags_without_bindings_are_untouched() {
+    let template_str = "<div  class='a'\n  id=x>{{value}}</div><p [unclosed>a < b";
+
+    let mut templates = Templates::new();
+    templates.load_str("test", template_str);
+
+    assert_eq!(
+        templates.render("test", &json!({"value": 1})),
+        "<div  class='a'\n  id=x>1</div><p [unclosed>a < b"
+    );
+}
+
+#[test]
+fn bindings_inside_blocks() {
+    let template_str = "@for (item of items) {<option [value]=\"item.id\" [selected]=\"item.id == current\">{{item.name}}</option>}";
+
+    let mut templates = Templates::new();
+    templates.load_str("test", template_str);
+
+    let ctx = json!({"current": 2, "items": [{"id": 1, "name": "a"}, {"id": 2, "name": "b"}]});
+
+    assert_eq!(
+        templates.render("test", &ctx),
+        "<option value=\"1\">a</option><option value=\"2\" selected>b</option>"
+    );
+}
+
+#[test]
+fn class_bindings_merge_with_static_class() {
+    let template_str = "<button class=\"btn {{size}} active\" [class.btn-primary]=\"primary\" [class.active]=\"selected\" [ngClass]=\"{'btn': true, 'wide loud': wide}\" type=\"button\">";
+
+    let mut templates = Templates::new();
+    templates.load_str("test", template_str);
+
+    assert_eq!(
+        templates.render(
+            "test",
+            &json!({"size": "lg", "primary": true, "wide": true})
+        ),
+        "<button class=\"btn lg btn-primary wide loud\" type=\"button\">"
+    );
+    assert_eq!(
+        templates.render("test", &json!({"size": "btn", "selected": true})),
+        "<button class=\"btn active\" type=\"button\">"
+    );
+}
+
+#[test]
+fn class_bindings_from_context() {
+    let template_str = "<li [ngClass]=\"classes\" [class.last]=\"index == 1\">";
+
+    let mut templates = Templates::new();
+    templates.load_str("test", template_str);
+
+    assert_eq!(
+        templates.render("test", &json!({"classes": "a b a", "index": 1})),
+        "<li class=\"a b last\">"
+    );
+    assert_eq!(
+        templates.render("test", &json!({"classes": ["a", "b"]})),
+        "<li class=\"a b\">"
+    );
+    assert_eq!(
+        templates.render("test", &json!({"classes": {"a": true, "b": false}})),
+        "<li class=\"a\">"
+    );
+    assert_eq!(templates.render("test", &json!({})), "<li>");
+}
+
+#[test]
+fn style_bindings_merge_with_static_style() {
+    let template_str = "<div style=\"color: red; width: 1px\" [style.width.px]=\"width\" [style.display]=\"display\" [ngStyle]=\"{'font-size.em': size, 'color': color}\">";
+
+    let mut templates = Templates::new();
+    templates.load_str("test", template_str);
+
+    assert_eq!(
+        templates.render("test", &json!({"width": 40, "size": 1.5})),
+        "<div style=\"color: red; width: 40px; font-size: 1.5em\">"
+    );
+    assert_eq!(
+        templates.render("test", &json!({"color": "blue"})),
+        "<div style=\"width: 1px; color: blue\">"
+    );
+    assert_eq!(
+        templates.render("test", &json!({"display": "none"})),
+        "<div style=\"color: red; width: 1px; display: none\">"
+    );
+}
+
+#[test]
+fn angular_only_attributes_are_dropped() {
+    let template_str = "<form #form=\"ngForm\" (ngSubmit)=\"save()\"><input [(ngModel)]=\"name\" name=\"name\"><h1 i18n i18n-title title=\"Hi\">Hi</h1></form>";
+
+    let mut templates = Templates::new();
+    templates.load_str("test", template_str);
+
+    assert_eq!(
+        templates.render("test", &json!({})),
+        "<form><input name=\"name\"><h1 title=\"Hi\">Hi</h1></form>"
+    );
+}
+
+#[test]
+fn angular_only_attributes_can_be_mapped() {
+    let template_str =
+        "<button class='btn' (click)=\"/items/{{id}}/save\" [disabled]=\"busy\">Save</button>";
+
+    let mut templates = Templates::new();
+    templates.map_attribute("(click)", "hx-post");
+    templates.load_str("test", template_str);
+
+    assert_eq!(
+        templates.render("test", &json!({"id": 3, "busy": false})),
+        "<button class=\"btn\" hx-post=\"/items/3/save\">Save</button>"
+    );
+}
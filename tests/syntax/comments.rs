@@ -0,0 +1,70 @@
+use serde_json::json;
+use skabelon::Templates;
+
+#[test]
+fn template_comments_are_not_rendered() {
+    let template_str = "<p>{{!-- internal: @if (a) { } {{name}} --}}Hi {{name}}</p>{{!--\nTODO\n--}}<!-- public -->";
+
+    let mut templates = Templates::new();
+    templates.load_str("test", template_str);
+
+    assert!(templates.validate().is_ok());
+    assert_eq!(
+        templates.render("test", &json!({"name": "Ann"})),
+        "<p>Hi Ann</p><!-- public -->"
+    );
+}
+
+#[test]
+fn unclosed_template_comment() {
+    let mut templates = Templates::new();
+    templates.load_str("test", "a\n{{!-- b");
+
+    let diagnostics = templates.validate().unwrap_err();
+    assert_eq!(
+        diagnostics[0].to_string(),
+        "test:2:1: unclosed comment, expected `--}}`"
+    );
+    assert_eq!(templates.render("test", &json!({})), "a\n");
+}
+
+#[test]
+fn html_comments_can_be_stripped() {
+    let template_str =
+        "@if (a) {<!-- note } -->x}<!--[if IE]><p>old</p><![endif]--><!--<![endif]-->";
+
+    let mut templates = Templates::new();
+    templates.set_strip_comments(true);
+    templates.load_str("test", template_str);
+
+    assert_eq!(
+        templates.render("test", &json!({"a": true})),
+        "x<!--[if IE]><p>old</p><![endif]--><!--<![endif]-->"
+    );
+}
+
+#[test]
+fn only_comments_in_markup_are_stripped() {
+    let template_str = concat!(
+        r#"<div title="<!-- not a comment -->" data-x='<!--'>a<!-- b --></div>"#,
+        r#"<script>var s = "<!-- x -->";</script>"#,
+        "<textarea><!-- kept --></textarea><!-- c -->",
+    );
+
+    for html in [false, true] {
+        let mut templates = Templates::new();
+        templates.set_html_mode(html);
+        templates.set_strip_comments(true);
+        templates.load_str("test", template_str);
+
+        assert!(templates.validate().is_ok());
+        assert_eq!(
+            templates.render("test", &json!({})),
+            concat!(
+                r#"<div title="<!-- not a comment -->" data-x='<!--'>a</div>"#,
+                r#"<script>var s = "<!-- x -->";</script>"#,
+                "<textarea><!-- kept --></textarea>",
+            )
+        );
+    }
+}
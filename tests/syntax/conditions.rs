@@ -1,5 +1,5 @@
 use serde_json::json;
-use skabelon::Templates;
+use skabelon::{Comparison, Templates};
 
 #[test]
 fn if_condition() {
@@ -446,3 +446,147 @@ fn if_objects() {
     let expected = "hello world";
     assert_eq!(output, expected);
 }
+
+#[test]
+fn in_array() {
+    let template_str = "@if('admin' in user.roles) {admin}@if(2 in numbers) { two}@if('root' in user.roles) { root}";
+
+    let mut templates = Templates::new();
+    templates.load_str("template", template_str);
+
+    let ctx = json!({"user": {"roles": ["editor", "admin"]}, "numbers": [1.0, 2.0]});
+
+    let output = templates.render("template", &ctx);
+
+    let expected = "admin two";
+
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn in_object_keys() {
+    let template_str = "@if('email' in user) {email}@if('phone' in user) { phone}";
+
+    let mut templates = Templates::new();
+    templates.load_str("template", template_str);
+
+    let ctx = json!({"user": {"email": "bob@example.com"}});
+
+    let output = templates.render("template", &ctx);
+
+    let expected = "email";
+
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn not_in() {
+    let template_str = "@if(tag not in tags) {new}@if(not ('a' in tags)) { no a}";
+
+    let mut templates = Templates::new();
+    templates.load_str("template", template_str);
+
+    let ctx = json!({"tag": "rust", "tags": ["go", "zig"]});
+
+    let output = templates.render("template", &ctx);
+
+    let expected = "new no a";
+
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn string_operators() {
+    let template_str = "@if(name startsWith 'A') {A}@if(name endsWith 'ce') {ce}@if(name contains 'lic') {lic}@if('li' in name) {li}@if(name startsWith 'B') {B}";
+
+    let mut templates = Templates::new();
+    templates.load_str("template", template_str);
+
+    let ctx = json!({"name": "Alice"});
+
+    let output = templates.render("template", &ctx);
+
+    let expected = "Acelicli";
+
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn contains_quoted_string_with_spaces() {
+    let template_str = "@if(title contains 'hello world') {found}";
+
+    let mut templates = Templates::new();
+    templates.load_str("template", template_str);
+
+    let ctx = json!({"title": "Say hello world!"});
+
+    let output = templates.render("template", &ctx);
+
+    let expected = "found";
+
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn integers_compare_exactly() {
+    let template_str = "@if(id == 9007199254740993) {exact}@if(id > 9007199254740992) { greater}@if(big > -1) { big}";
+
+    let mut templates = Templates::new();
+    templates.load_str("template", template_str);
+
+    let ctx = json!({"id": 9007199254740993_u64, "big": u64::MAX});
+
+    let output = templates.render("template", &ctx);
+
+    let expected = "exact greater big";
+
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn strict_comparison() {
+    let template_str =
+        "@if(count < 6) {lt}@if(count == 5) {eq}@if(flag == 1) {flag}@if(missing != 1) {missing}";
+
+    let mut templates = Templates::new();
+    templates.load_str("template", template_str);
+
+    let ctx = json!({"count": "5", "flag": true});
+
+    let output = templates.render("template", &ctx);
+
+    let expected = "missing";
+
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn loose_comparison() {
+    let template_str = "@if(count < 6) {lt}@if(count == 5) { eq}@if(flag == 1) { flag}@if(name == 0) { name}@if(nothing == 0) { null}@if(nothing < 1) { null-lt}";
+
+    let mut templates = Templates::new();
+    templates.set_comparison(Comparison::Loose);
+    templates.load_str("template", template_str);
+
+    let ctx = json!({"count": "5", "flag": true, "name": "Bob", "nothing": null});
+
+    let output = templates.render("template", &ctx);
+
+    let expected = "lt eq flag null-lt";
+
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn literal_truthiness() {
+    let template_str = "@if(0) {zero}@if(1) {one}@if('') {empty}@if('0') { string}@if(!0) { not-zero}@if(0 || 'a') { or}";
+
+    let mut templates = Templates::new();
+    templates.load_str("template", template_str);
+
+    let output = templates.render("template", &json!({}));
+
+    let expected = "one string not-zero or";
+
+    assert_eq!(output, expected);
+}
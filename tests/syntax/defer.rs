@@ -188,3 +188,16 @@ fn partial_with_variable_context() {
 
     assert_eq!(output, expected);
 }
+
+#[test]
+fn defer_block_renders_in_place() {
+    let mut templates = Templates::new();
+    templates.load_str(
+        "page",
+        "@defer {<p>{{value}}</p>} @placeholder {wait} @error {error} after",
+    );
+
+    let output = templates.render("page", &json!({"value": "now"}));
+
+    assert_eq!(output, "<p>now</p> after");
+}
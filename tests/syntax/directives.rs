@@ -0,0 +1,104 @@
+use serde_json::json;
+use skabelon::Templates;
+
+#[test]
+fn ng_if_with_else_template() {
+    let template_str = "<div *ngIf=\"user; else guest\" class=\"user\">{{user.name}}</div><ng-template #guest><a href=\"/login\">Log in</a></ng-template>";
+
+    let mut templates = Templates::new();
+    templates.load_str("test", template_str);
+
+    assert_eq!(
+        templates.render("test", &json!({"user": {"name": "Ann"}})),
+        "<div class=\"user\">Ann</div>"
+    );
+    assert_eq!(
+        templates.render("test", &json!({})),
+        "<a href=\"/login\">Log in</a>"
+    );
+}
+
+#[test]
+fn ng_if_with_then_template() {
+    let template_str = "<ng-container *ngIf=\"admin; then panel; else plain\"></ng-container><ng-template #panel>panel</ng-template><ng-template #plain>plain</ng-template>";
+
+    let mut templates = Templates::new();
+    templates.load_str("test", template_str);
+
+    assert_eq!(templates.render("test", &json!({"admin": true})), "panel");
+    assert_eq!(templates.render("test", &json!({"admin": false})), "plain");
+}
+
+#[test]
+fn ng_for_with_index() {
+    let template_str = "<ul><li *ngFor=\"let item of items; let i = index; trackBy: byId\" [class.first]=\"i == 0\">{{i}}: {{item}}</li></ul>";
+
+    let mut templates = Templates::new();
+    templates.load_str("test", template_str);
+
+    assert_eq!(
+        templates.render("test", &json!({"items": ["a", "b"]})),
+        "<ul><li class=\"first\">0: a</li><li>1: b</li></ul>"
+    );
+}
+
+#[test]
+fn ng_container_renders_its_content() {
+    let template_str = "<ng-container *ngFor=\"let row of rows\"><ng-container *ngIf=\"row.visible\">{{row.name}};</ng-container></ng-container>";
+
+    let mut templates = Templates::new();
+    templates.load_str("test", template_str);
+
+    let ctx = json!({"rows": [{"name": "a", "visible": true}, {"name": "b"}, {"name": "c", "visible": true}]});
+    assert_eq!(templates.render("test", &ctx), "a;c;");
+}
+
+#[test]
+fn unknown_template_and_unclosed_tags() {
+    let mut templates = Templates::new();
+    templates.load_str(
+        "test",
+        "<p *ngIf=\"x; else missing\">x</p>\n<div *ngIf=\"y\">",
+    );
+
+    let diagnostics: Vec<String> = templates
+        .validate()
+        .unwrap_err()
+        .iter()
+        .map(|d| d.to_string())
+        .collect();
+    assert_eq!(
+        diagnostics,
+        vec![
+            "test:2:1: unclosed `<div>`",
+            "test:1:1: unknown template `#missing`"
+        ]
+    );
+}
+
+#[test]
+fn ng_if_as_is_rejected() {
+    let mut templates = Templates::new();
+    templates.load_str("test", "<p *ngIf=\"user as u\">{{u.name}}</p>");
+
+    let diagnostics = templates.validate().unwrap_err();
+    assert_eq!(
+        diagnostics[0].to_string(),
+        "test:1:1: `as` in `*ngIf` is not supported, use `user` in the body"
+    );
+}
+
+#[test]
+fn semicolons_in_strings_do_not_split_directives() {
+    let template_str =
+        "<i *ngFor=\"let x of xs; where x != 'a;b'\">{{x}}</i><b *ngIf=\"s == 'a;b'\">{{s}}</b>";
+
+    let mut templates = Templates::new();
+    templates.load_str("test", template_str);
+
+    assert!(templates.validate().is_ok());
+    assert_eq!(
+        templates.render("test", &json!({"xs": ["a", "a;b"], "s": "a;b"})),
+        "<i>a</i><b>a;b</b>"
+    );
+}
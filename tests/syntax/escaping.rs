@@ -0,0 +1,30 @@
+use serde_json::json;
+use skabelon::Templates;
+
+#[test]
+fn keywords_only_match_on_their_own() {
+    let template_str = "Mail info@for-sale.com, info@for sale, me@if (a) (x), @iffy or @format.@if(a){yes}@else{no} @for (x in xs) {{{x}}}";
+
+    let mut templates = Templates::new();
+    templates.load_str("test", template_str);
+
+    assert!(templates.validate().is_ok());
+    assert_eq!(
+        templates.render("test", &json!({"a": false, "xs": [1, 2]})),
+        "Mail info@for-sale.com, info@for sale, me@if (a) (x), @iffy or @format.no 12"
+    );
+}
+
+#[test]
+fn entities_and_literals_escape_control_characters() {
+    let template_str = "@if (a) {&#64;if (x) &#123; y &#125; {{ '}' }}{{ \"{{ }}\" }}}";
+
+    let mut templates = Templates::new();
+    templates.load_str("test", template_str);
+
+    assert!(templates.validate().is_ok());
+    assert_eq!(
+        templates.render("test", &json!({"a": true})),
+        "&#64;if (x) &#123; y &#125; }{{ }}"
+    );
+}
@@ -0,0 +1,71 @@
+use serde_json::json;
+use skabelon::Templates;
+
+fn html_templates() -> Templates {
+    let mut templates = Templates::new();
+    templates.set_html_mode(true);
+    templates
+}
+
+#[test]
+fn braces_in_raw_text_and_attributes() {
+    let template_str = "@if (show) {<style>p { color: red }</style><p style=\"x}\" title='{{title}}'>x</p><script>if (a) { b() }</script><!-- } @if -->}";
+
+    let mut templates = html_templates();
+    templates.load_str("test", template_str);
+
+    assert_eq!(templates.validate().map_err(|d| d[0].to_string()), Ok(()));
+    assert_eq!(
+        templates.render("test", &json!({"show": true, "title": "T"})),
+        "<style>p { color: red }</style><p style=\"x}\" title='T'>x</p><script>if (a) { b() }</script><!-- } @if -->"
+    );
+}
+
+#[test]
+fn text_mode_is_the_default() {
+    let mut templates = Templates::new();
+    templates.load_str("test", "@if (show) {<style>p { color: red }</style>}");
+
+    assert_eq!(
+        templates.render("test", &json!({"show": true})),
+        "<style>p { color: red </style>}"
+    );
+}
+
+#[test]
+fn elements_must_close_in_their_block() {
+    let template_str = "@if (a) {<div>} @else {<div class=\"b\">}\n</div><ul><li>one<li>two</ul><br><img src=\"x\"/><span [title]=\"t\"><b></span>";
+
+    let mut templates = html_templates();
+    templates.load_str("test", template_str);
+
+    let diagnostics: Vec<String> = templates
+        .validate()
+        .unwrap_err()
+        .iter()
+        .map(|d| d.to_string())
+        .collect();
+    assert_eq!(
+        diagnostics,
+        vec![
+            "test:1:10: unclosed `<div>`",
+            "test:1:24: unclosed `<div>`",
+            "test:2:1: unexpected `</div>`",
+            "test:2:66: unclosed `<b>`",
+        ]
+    );
+}
+
+#[test]
+fn unclosed_start_tags_and_comments() {
+    let mut templates = html_templates();
+    templates.load_str("test", "<p title=\"a>\n<!-- b");
+
+    let diagnostics: Vec<String> = templates
+        .validate()
+        .unwrap_err()
+        .iter()
+        .map(|d| d.to_string())
+        .collect();
+    assert_eq!(diagnostics, vec!["test:1:1: unclosed start tag `<p`"]);
+}
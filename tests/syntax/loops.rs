@@ -1,5 +1,5 @@
 use serde_json::json;
-use skabelon::Templates;
+use skabelon::{Error, Templates};
 
 #[test]
 fn for_loops() {
@@ -41,3 +41,196 @@ fn index() {
 
     assert_eq!(output, expected);
 }
+
+#[test]
+fn ranges() {
+    let template_str = "@for(i in range(1, pages + 1)) {[{{i}}]}|@for(i in range(3)) {{{i}}}|@for(i in range(10, 0, -3)) {{{i}},}|@for(i in range(0, 10, step)) {{{index}}:{{i}} }";
+
+    let mut templates = Templates::new();
+    templates.load_str("test", template_str);
+
+    let ctx = json!({"pages": 3, "step": 4});
+
+    let output = templates.render("test", &ctx);
+
+    let expected = "[1][2][3]|012|10,7,4,1,|0:0 1:4 2:8 ";
+
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn range_arithmetic() {
+    let template_str = "@for(i in range(total / 2 - 1, total * 2 % 7)) {{{i}}}@if(total % 2) { odd}@if(7 / 2 == 3.5) { half}";
+
+    let mut templates = Templates::new();
+    templates.load_str("test", template_str);
+
+    assert_eq!(
+        templates.try_render("test", &json!({"total": 6})),
+        Ok("234 half".to_string())
+    );
+    assert_eq!(
+        templates.try_render("test", &json!({"total": 5})),
+        Err(Error::Render("`range` expects integers".into()))
+    );
+    assert_eq!(templates.render("test", &json!({"total": 5})), " odd half");
+}
+
+#[test]
+fn loop_over_string_is_an_error() {
+    let template_str = "@for(c in name) {{{c}}}";
+
+    let mut templates = Templates::new();
+    templates.load_str("test", template_str);
+
+    let ctx = json!({"name": "Bob"});
+
+    assert_eq!(templates.render("test", &ctx), "");
+    assert_eq!(
+        templates.try_render("test", &ctx),
+        Err(Error::Render(
+            "`@for` over `name` expects an array, got a string".into()
+        ))
+    );
+    assert_eq!(templates.try_render("test", &json!({})), Ok(String::new()));
+}
+
+#[test]
+fn of_and_track() {
+    let template_str = "@for (user of users; track user.id) {<li>{{user.name}}</li>}";
+
+    let mut templates = Templates::new();
+    templates.load_str("test", template_str);
+    assert!(templates.validate().is_ok());
+
+    let ctx = json!({"users": [{"id": 1, "name": "Ann"}, {"id": 2, "name": "Bob"}]});
+
+    assert_eq!(templates.render("test", &ctx), "<li>Ann</li><li>Bob</li>");
+}
+
+#[test]
+fn duplicate_track_keys_in_strict_mode() {
+    let template_str =
+        "@for (user of users; track user.id) {{{user.name}}}@for (user of users; track $index) {}";
+
+    let mut templates = Templates::new();
+    templates.load_str("test", template_str);
+
+    let ctx = json!({"users": [{"id": 1, "name": "Ann"}, {"id": 1, "name": "Bob"}]});
+
+    assert_eq!(templates.try_render("test", &ctx), Ok("AnnBob".to_string()));
+
+    templates.set_strict(true);
+    assert_eq!(
+        templates.try_render("test", &ctx),
+        Err(Error::Render(
+            "`@for` has two items with the `track` key 1".into()
+        ))
+    );
+    assert_eq!(templates.render("test", &ctx), "AnnBob");
+}
+
+#[test]
+fn unknown_for_option() {
+    let mut templates = Templates::new();
+    templates.load_str("test", "@for (item of items; trak item) {}");
+
+    let diagnostics = templates.validate().unwrap_err();
+    assert_eq!(
+        diagnostics[0].to_string(),
+        "test:1:1: unknown `@for` option `trak item`"
+    );
+}
+
+#[test]
+fn where_and_limit() {
+    let template_str = "@for (p of posts; where p.published; limit 2) {{{index}}:{{p.title}} }|@for (i in range(10); where i % 3 == 0; limit max) {{{i}}}|@for (p of posts; limit 0) {never}";
+
+    let mut templates = Templates::new();
+    templates.load_str("test", template_str);
+
+    let ctx = json!({
+        "max": 3,
+        "posts": [
+            {"title": "a", "published": true},
+            {"title": "b", "published": false},
+            {"title": "c", "published": true},
+            {"title": "d", "published": true},
+        ]
+    });
+
+    assert_eq!(templates.render("test", &ctx), "0:a 1:c |036|");
+}
+
+#[test]
+fn invalid_limit() {
+    let mut templates = Templates::new();
+    templates.load_str("test", "@for (item of items; limit -1) {{{item}}}");
+
+    let ctx = json!({"items": [1, 2]});
+    assert_eq!(templates.render("test", &ctx), "");
+    assert_eq!(
+        templates.try_render("test", &ctx),
+        Err(Error::Render(
+            "`limit` expects a non-negative integer".into()
+        ))
+    );
+}
+
+#[test]
+fn nested_loops_and_partials_see_loop_items() {
+    let template_str =
+        "@for (row of table) {@for (col of row.cols) {{{col}}}@defer (cell; row=row) {}|}";
+
+    let mut templates = Templates::new();
+    templates.load_str("test", template_str);
+    templates.load_str("cell", "@for (col of row.cols; limit 1) {<{{col}}>}");
+
+    let ctx = json!({"table": [{"cols": [1, 2]}, {"cols": [3]}]});
+
+    assert_eq!(templates.render("test", &ctx), "12<1>|3<3>|");
+}
+
+#[test]
+fn loop_variable_aliases() {
+    let template_str = "@for (n of nums; where n > 1; let i = $index, last = $last, odd = $odd, count = $count) {{{i}}/{{count}}={{n}}@if (odd) {*}@if (!last) {, }}";
+
+    let mut templates = Templates::new();
+    templates.load_str("test", template_str);
+
+    assert_eq!(
+        templates.render("test", &json!({"nums": [1, 2, 3, 4]})),
+        "0/3=2, 1/3=3*, 2/3=4"
+    );
+}
+
+#[test]
+fn count_of_large_ranges() {
+    let template_str = concat!(
+        "@for (i in range(0, 9000000000000000000); limit 3; let c = $count, last = $last) {{{i}}/{{c}}@if (!last) {,}}|",
+        "@for (i in range(0, 9000000000000000000); where i % 2; limit 2; let c = $count) {{{i}}/{{c}} }|",
+        "@for (i in range(10, 0, -3); let c = $count) {{{c}}}",
+    );
+
+    let mut templates = Templates::new();
+    templates.load_str("test", template_str);
+
+    assert_eq!(
+        templates.render("test", &json!({})),
+        "0/3,1/3,2/3|1/2 3/2 |4444"
+    );
+}
+
+#[test]
+fn semicolons_in_strings_do_not_split_options() {
+    let template_str = "@for (x of xs; where x == 'a;b'; let c = $count) {{{x}}/{{c}}}";
+
+    let mut templates = Templates::new();
+    templates.load_str("test", template_str);
+
+    assert!(templates.validate().is_ok());
+    assert_eq!(
+        templates.render("test", &json!({"xs": ["a", "a;b", "b"]})),
+        "a;b/1"
+    );
+}
@@ -1,5 +1,11 @@
 mod arrays;
+mod attributes;
+mod comments;
 mod conditions;
 mod defer;
+mod directives;
+mod escaping;
+mod html;
 mod loops;
 mod objects;
+mod whitespace;
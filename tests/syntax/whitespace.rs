@@ -0,0 +1,99 @@
+use serde_json::json;
+use skabelon::Templates;
+
+#[test]
+fn collapsed_whitespace() {
+    let template_str = "
+<ul>
+  @for (item in items) {
+    <li>  {{item}}  and   more </li>
+  }
+</ul>
+<pre>
+  kept  {{ items[0] }}
+</pre>
+<p>{{a}} {{b}}</p>
+";
+
+    let mut templates = Templates::new();
+    templates.set_preserve_whitespace(false);
+    templates.load_str("test", template_str);
+
+    assert_eq!(
+        templates.render("test", &json!({"items": ["x", "y"], "a": 1, "b": 2})),
+        "<ul><li> x and more </li><li> y and more </li></ul><pre>\n  kept  x\n</pre><p>1 2</p>"
+    );
+}
+
+#[test]
+fn trim_markers() {
+    let template_str = "Hello {{- name -}} !
+~@if (vip) {~
+  You are a VIP.
+~}~
+@for (line in lines) {~
+  - {{line}}
+}~
+Bye";
+
+    let mut templates = Templates::new();
+    templates.load_str("test", template_str);
+
+    assert_eq!(
+        templates.render(
+            "test",
+            &json!({"name": "Ann", "vip": true, "lines": ["a", "b"]})
+        ),
+        "HelloAnn!You are a VIP.- a\n- b\nBye"
+    );
+}
+
+#[test]
+fn minified_output() {
+    let template_str = "<!DOCTYPE html>
+<html lang=\"en\">
+  <!-- layout -->
+  <body class=\"page home\" id='main' data-x=\"\">
+    <a href=\"/users/{{id}}\" title=\"a/\" target=\"_blank\">Profile</a>
+    <input [value]=\"name\" type=\"text\">
+    <script>if (a) { x = \"y\" }</script>
+    <textarea rows=\"2\">
+  kept
+</textarea>
+  </body>
+</html>
+";
+
+    let mut templates = Templates::new();
+    templates.set_minify(true);
+    templates.load_str("test", template_str);
+
+    assert_eq!(
+        templates.render("test", &json!({"id": 7, "name": "Ann"})),
+        "<!DOCTYPE html><html lang=en><body class=\"page home\" id=main data-x=\"\"><a href=\"/users/7\" title=\"a/\" target=\"_blank\">Profile</a><input value=\"Ann\" type=\"text\"><script>if (a) { x = \"y\" }</script><textarea rows=\"2\">\n  kept\n</textarea></body></html>"
+    );
+}
+
+#[test]
+fn minify_keeps_comments_in_text_elements() {
+    let template_str = concat!(
+        "<script>var s = \"<!-- x -->\";</script>\n",
+        "<pre> a <!-- keep --> b</pre>\n",
+        "<textarea><!-- kept --></textarea>\n",
+        "<p>a <!-- gone --> b</p>",
+    );
+
+    let mut templates = Templates::new();
+    templates.set_minify(true);
+    templates.load_str("test", template_str);
+
+    assert_eq!(
+        templates.render("test", &json!({})),
+        concat!(
+            "<script>var s = \"<!-- x -->\";</script>",
+            "<pre> a <!-- keep --> b</pre>",
+            "<textarea><!-- kept --></textarea>",
+            "<p>a b</p>",
+        )
+    );
+}
@@ -0,0 +1,94 @@
+use skabelon::Templates;
+
+#[test]
+fn valid_templates() {
+    let mut templates = Templates::new();
+    templates.load_str("parent", "@defer(partial) {Hello} @if(true) {World}");
+    templates.load_str("partial", "<div>{{ content }}</div>");
+
+    assert!(templates.validate().is_ok());
+}
+
+#[test]
+fn missing_defer_target() {
+    let mut templates = Templates::new();
+    templates.load_str("parent", "@if(true) {@defer(missing) {}}");
+
+    let diagnostics = templates.validate().unwrap_err();
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].template, "parent");
+    assert_eq!(
+        diagnostics[0].to_string(),
+        "parent: `@defer` target `missing` does not exist"
+    );
+}
+
+#[test]
+fn unclosed_block() {
+    let mut templates = Templates::new();
+    templates.load_str("test", "<h1>Hello</h1>\n@if(show) {\n<p>Visible</p>");
+
+    let diagnostics = templates.validate().unwrap_err();
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(
+        diagnostics[0].to_string(),
+        "test:2:11: unclosed block, expected `}`"
+    );
+}
+
+#[test]
+fn unclosed_interpolation() {
+    let mut templates = Templates::new();
+    templates.load_str("test", "Hello {{ name");
+
+    let diagnostics = templates.validate().unwrap_err();
+
+    assert_eq!(
+        diagnostics[0].to_string(),
+        "test:1:7: unclosed interpolation, expected `}}`"
+    );
+}
+
+#[test]
+fn stray_else() {
+    let mut templates = Templates::new();
+    templates.load_str("test", "Hello @else {World}");
+
+    let diagnostics = templates.validate().unwrap_err();
+
+    assert_eq!(diagnostics[0].message, "`@else` without a matching `@if`");
+}
+
+#[test]
+fn reloading_clears_errors() {
+    let mut templates = Templates::new();
+    templates.load_str("test", "@if(show) {");
+    assert!(templates.validate().is_err());
+
+    templates.load_str("test", "@if(show) {}");
+    assert!(templates.validate().is_ok());
+}
+
+#[test]
+fn unclosed_blocks_after_non_ascii_text() {
+    for template_str in [
+        "Vi har tilbud @for alle på",
+        "Se @if du vil, så",
+        "@for{ø",
+        "@if(\"",
+    ] {
+        let mut templates = Templates::new();
+        templates.load_str("test", template_str);
+
+        assert!(templates.validate().is_err(), "{}", template_str);
+    }
+
+    let mut templates = Templates::new();
+    templates.load_str("test", "Æøå @if (a) {ø");
+    assert_eq!(
+        templates.validate().unwrap_err()[0].to_string(),
+        "test:1:13: unclosed block, expected `}`"
+    );
+}